@@ -0,0 +1,47 @@
+// Runs the fake Discord client used by the tests, printing every activity it receives
+// as a JSON line, so the plugin can be tried out without running Discord:
+//
+//   XDG_RUNTIME_DIR=/tmp/fake-discord cargo run --example fake_discord [record.jsonl]
+//   XDG_RUNTIME_DIR=/tmp/fake-discord mpv song.flac
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+// Shared with the tests, the plugin is only built as a C plugin
+#[allow(dead_code)]
+#[path = "../src/discord_client/fake_discord.rs"]
+mod fake_discord;
+
+use fake_discord::FakeDiscord;
+
+fn main() -> io::Result<()> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    let socket_path = PathBuf::from(runtime_dir).join("discord-ipc-0");
+
+    let mut record = match env::args().nth(1) {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None
+    };
+
+    let discord = FakeDiscord::bind(&socket_path)?;
+    eprintln!("Listening on {}", socket_path.display());
+
+    let mut printed = 0;
+    loop {
+        let activities = discord.get_activities();
+        for activity in &activities[printed..] {
+            println!("{activity}");
+
+            if let Some(ref mut record) = record {
+                writeln!(record, "{activity}")?;
+            }
+        }
+
+        printed = activities.len();
+        thread::sleep(Duration::from_millis(100));
+    }
+}
@@ -2,6 +2,8 @@
 
 key='D'
 script_binding='script-binding "libmpv_rpc/toggle-rpc"'
+privacy_key='Alt+d'
+privacy_binding='script-binding "libmpv_rpc/toggle-privacy"'
 mpv_home="${MPV_HOME:-${XDG_CONFIG_HOME:-${HOME}/.config}/mpv}"
 scripts_dir="$mpv_home/scripts"
 
@@ -23,4 +25,10 @@ if ! grep -q "$script_binding" "$mpv_home/input.conf"; then
     echo -n "Adding keybinding entry to input.conf..."
     echo "$key $script_binding" >> "$mpv_home/input.conf"
     echo "Done!"
+fi
+
+if ! grep -q "$privacy_binding" "$mpv_home/input.conf"; then
+    echo -n "Adding privacy keybinding entry to input.conf..."
+    echo "$privacy_key $privacy_binding" >> "$mpv_home/input.conf"
+    echo "Done!"
 fi
\ No newline at end of file
@@ -1,88 +1,581 @@
 use std::env;
 use std::fs;
-use serde::{self, Serialize, Deserialize};
+use serde::{self, Serialize, Deserialize, Deserializer};
+use serde::de::Error;
+use serde_json::{Map, Value};
 use crate::logging::{self, Logger};
 
+pub mod watcher;
+pub mod paths;
+mod script_opts;
+mod validation;
+
+use paths::ConfigPaths;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum CoverArtProviderName {
+    #[serde(rename = "musicbrainz")]
+    MusicBrainz,
+
+    #[serde(rename = "itunes")]
+    ITunes
+}
+
+// Label templates for the activity buttons, a missing label disables the button.
+// {host} is replaced with the host name of the link
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ButtonsConfig {
+    #[serde(default = "release_button_default")]
+    pub release: Option<String>,
+
+    #[serde(default = "source_button_default")]
+    pub source: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum TimestampsMode {
+    #[serde(rename = "remaining")]
+    Remaining,
+
+    #[serde(rename = "elapsed")]
+    Elapsed
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum ActivityTypeName {
+    #[serde(rename = "playing")]
+    Playing,
+
+    #[serde(rename = "listening")]
+    Listening,
+
+    #[serde(rename = "watching")]
+    Watching,
+
+    #[serde(rename = "competing")]
+    Competing
+}
+
+// Activity type shown for each kind of media, e.g. "Listening to mpv"
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ActivityTypesConfig {
+    #[serde(default = "audio_activity_type_default")]
+    pub audio: ActivityTypeName,
+
+    #[serde(default = "video_activity_type_default")]
+    pub video: ActivityTypeName
+}
+
+// What is published for files matching the blocklist or while privacy mode is on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PrivacyAction {
+    // Clears the presence
+    #[serde(rename = "hide")]
+    Hide,
+
+    // Shows "Watching something" or "Listening to something"
+    #[serde(rename = "generic")]
+    Generic
+}
+
+// Each rule has a single key, like {"path": "~/Videos/Private"}
+#[derive(Serialize, Deserialize, Clone)]
+pub enum BlockRule {
+    // Files in the directory or any of its subdirectories
+    #[serde(rename = "path")]
+    Path(String),
+
+    // Glob matched against the file name, like "*.secret.mkv"
+    #[serde(rename = "filename")]
+    Filename(String),
+
+    // Streams from the host or any of its subdomains
+    #[serde(rename = "host")]
+    Host(String),
+
+    // Regex matched against the artist, album and title
+    #[serde(rename = "metadata")]
+    Metadata(String)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PrivacyConfig {
+    #[serde(default = "privacy_action_default")]
+    pub action: PrivacyAction,
+
+    #[serde(default)]
+    pub blocklist: Vec<BlockRule>
+}
+
+pub const DISCORD_APPID: &str = "1071519995588264016";
+
+// Asset keys of the Discord application, as uploaded to its Rich Presence art assets
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AssetsConfig {
+    // Shown when there is no cover art
+    #[serde(default = "large_image_default", deserialize_with = "deserialize_non_empty")]
+    pub large_image: String,
+
+    // Used when the large_text template is empty
+    #[serde(default = "large_text_default", deserialize_with = "deserialize_non_empty")]
+    pub large_text: String,
+
+    // Shown in the corner of the large image, none by default
+    #[serde(default, deserialize_with = "deserialize_non_empty_option")]
+    pub playing_image: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_non_empty_option")]
+    pub paused_image: Option<String>
+}
+
+pub const DETAILS_TEMPLATE: &str = "{title|video_title|filename}{? ({year})}{? [T{track}]}";
+pub const STATE_TEMPLATE: &str = "{?by {artist}}{? on {album}}{? S{season}}{? E{episode}}";
+pub const LARGE_TEXT_TEMPLATE: &str = "{title|album}";
+
+// Templates for the activity text, see template.rs for the syntax
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TemplatesConfig {
+    #[serde(default = "details_template_default")]
+    pub details: String,
+
+    #[serde(default = "state_template_default")]
+    pub state: String,
+
+    #[serde(default = "large_text_template_default")]
+    pub large_text: String
+}
+
 enum ConfigError {
     CannotLoad,
     ParseError(serde_json::Error)
 }
 
+// Files with syntax errors are skipped, the other sources still apply
+struct LoadedConfig {
+    config: Config,
+    issues: Vec<String>,
+    syntax_errors: Vec<String>
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "active_default")]
     pub active: bool,
 
+    // Discord application the presence is published as
+    #[serde(default = "application_id_default", deserialize_with = "deserialize_non_empty")]
+    pub application_id: String,
+
+    #[serde(default)]
+    pub assets: AssetsConfig,
+
     #[serde(default = "cover_art_default")]
-    pub cover_art: bool
+    pub cover_art: bool,
+
+    #[serde(default = "timestamps_default")]
+    pub timestamps: TimestampsMode,
+
+    #[serde(default = "cover_art_providers_default")]
+    pub cover_art_providers: Vec<CoverArtProviderName>,
+
+    #[serde(default = "cover_art_cache_default")]
+    pub cover_art_cache: bool,
+
+    #[serde(default)]
+    pub buttons: ButtonsConfig,
+
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+
+    #[serde(default)]
+    pub activity_types: ActivityTypesConfig,
+
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+
+    // File to record mpv events to, for reproducing issues
+    #[serde(default)]
+    pub trace: Option<String>,
+
+    #[serde(skip)]
+    pub paths: ConfigPaths
 }
 
 const fn active_default() -> bool {
     false
 }
 
+fn application_id_default() -> String {
+    DISCORD_APPID.to_string()
+}
+
+fn large_image_default() -> String {
+    "logo".to_string()
+}
+
+fn large_text_default() -> String {
+    "mpv".to_string()
+}
+
 const fn cover_art_default() -> bool {
     true
 }
 
+const fn timestamps_default() -> TimestampsMode {
+    TimestampsMode::Remaining
+}
+
+fn cover_art_providers_default() -> Vec<CoverArtProviderName> {
+    vec![CoverArtProviderName::MusicBrainz]
+}
+
+const fn cover_art_cache_default() -> bool {
+    true
+}
+
+fn release_button_default() -> Option<String> {
+    Some("View on MusicBrainz".to_string())
+}
+
+fn source_button_default() -> Option<String> {
+    Some("Open on {host}".to_string())
+}
+
+const fn audio_activity_type_default() -> ActivityTypeName {
+    ActivityTypeName::Listening
+}
+
+const fn video_activity_type_default() -> ActivityTypeName {
+    ActivityTypeName::Watching
+}
+
+const fn privacy_action_default() -> PrivacyAction {
+    PrivacyAction::Generic
+}
+
+fn details_template_default() -> String {
+    DETAILS_TEMPLATE.to_string()
+}
+
+fn state_template_default() -> String {
+    STATE_TEMPLATE.to_string()
+}
+
+fn large_text_template_default() -> String {
+    LARGE_TEXT_TEMPLATE.to_string()
+}
+
+// Discord rejects activities with empty asset keys
+fn deserialize_non_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.trim().is_empty() {
+        true => Err(D::Error::custom("value can't be empty")),
+        false => Ok(value)
+    }
+}
+
+fn deserialize_non_empty_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) if value.trim().is_empty() => Err(D::Error::custom("value can't be empty, use null to disable it")),
+        value => Ok(value)
+    }
+}
+
 impl Config {
-    pub fn from_config_file(logger: &Logger) -> Self {
-        let path = Config::get_config_path();
-        logging::info!(logger, "Config path {path}");
-        
-        match Config::parse_config_from_file(&path) {
-            Ok(config) => config,
-            Err(ConfigError::CannotLoad) => {
-                logging::info!(logger, "Cannot load config. Using default options");
-                Config::default()
+    // Options from --script-opts and script-opts/rpc.conf take precedence over rpc.json.
+    // Also returns the issues found in the config, to show them to the user
+    pub fn from_config_file(paths: &ConfigPaths, script_opts: Option<&str>, logger: &Logger) -> (Self, Vec<String>) {
+        match Config::load(paths, script_opts, logger) {
+            Ok(loaded) => {
+                let issues = loaded.syntax_errors.into_iter()
+                                                 .chain(loaded.issues)
+                                                 .collect();
+                (loaded.config, issues)
             }
-            Err(ConfigError::ParseError(e)) => {
-                logging::error!(logger, "Cannot parse config: {e}");
-                Config::default()
+            Err(e) => {
+                logging::error!(logger, "Cannot parse config {e}");
+                let config = Config {
+                    paths: paths.clone(),
+                    ..Config::default()
+                };
+                (config, vec![e])
             }
         }
     }
 
-    fn parse_config_from_file(path: &str) -> Result<Self, ConfigError> {
+    // Unlike at startup, a broken config is reported instead of skipped,
+    // so a typo doesn't undo the settings in use
+    pub fn reload_config_file(paths: &ConfigPaths, script_opts: Option<&str>, logger: &Logger) -> Result<(Self, Vec<String>), String> {
+        let loaded = Config::load(paths, script_opts, logger)?;
+        match loaded.syntax_errors.is_empty() {
+            true => Ok((loaded.config, loaded.issues)),
+            false => {
+                logging::error!(logger, "Keeping the previous config");
+                Err(loaded.syntax_errors.join("\n"))
+            }
+        }
+    }
+
+    // Syntax errors only skip the file they are in, invalid fields are dropped and reported
+    fn load(paths: &ConfigPaths, script_opts: Option<&str>, logger: &Logger) -> Result<LoadedConfig, String> {
+        let mut config = Value::Object(Map::new());
+        let mut issues = Vec::new();
+        let mut syntax_errors = Vec::new();
+        let mut loaded = false;
+
+        // Values are validated and converted against the defaults
+        let defaults = match serde_json::to_value(Config::default()) {
+            Ok(defaults) => defaults,
+            Err(e) => return Err(e.to_string())
+        };
+
+        for path in &paths.configs {
+            match Config::parse_config_from_file(path) {
+                Ok((json, mut values)) => {
+                    logging::info!(logger, "Config path {path}");
+                    issues.extend(validation::validate::<Config>(&mut values, &defaults, path, Some(&json)));
+                    Config::merge(&mut config, values);
+                    loaded = true;
+                }
+                Err(ConfigError::CannotLoad) => (),
+                Err(ConfigError::ParseError(e)) => {
+                    logging::error!(logger, "Cannot parse config {path}: {e}");
+                    syntax_errors.push(format!("{path}: {e}"));
+                }
+            }
+        }
+
+        if !loaded {
+            logging::info!(logger, "Cannot load config. Using default options");
+        }
+
+        if let Ok(conf) = fs::read_to_string(&paths.script_opts) {
+            let options = script_opts::parse_conf(&conf, &paths.script_opts, &mut issues);
+            let values = Config::from_options(&options, &defaults, &paths.script_opts, &mut issues);
+            Config::merge(&mut config, values);
+        }
+
+        if let Some(script_opts) = script_opts {
+            let options = script_opts::parse_script_opts(script_opts);
+            let values = Config::from_options(&options, &defaults, "--script-opts", &mut issues);
+            Config::merge(&mut config, values);
+        }
+
+        for issue in &issues {
+            logging::warning!(logger, "Invalid config {issue}");
+        }
+
+        match serde_json::from_value::<Config>(config) {
+            Ok(config) => Ok(LoadedConfig {
+                config: Config { paths: paths.clone(), ..config },
+                issues,
+                syntax_errors
+            }),
+            Err(e) => Err(e.to_string())
+        }
+    }
+
+    fn from_options(options: &[(String, String)], defaults: &Value, name: &str, issues: &mut Vec<String>) -> Value {
+        let mut values = Value::Object(Map::new());
+        script_opts::apply(&mut values, defaults, options, name, issues);
+        issues.extend(validation::validate::<Config>(&mut values, defaults, name, None));
+        values
+    }
+
+    // Objects are merged key by key, anything else is replaced
+    fn merge(config: &mut Value, values: Value) {
+        match (config, values) {
+            (Value::Object(config), Value::Object(values)) => {
+                for (key, value) in values {
+                    match config.get_mut(&key) {
+                        Some(existing) => Config::merge(existing, value),
+                        None => {
+                            config.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (config, values) => *config = values
+        }
+    }
+
+    // The source is kept to find the position of invalid fields
+    fn parse_config_from_file(path: &str) -> Result<(String, Value), ConfigError> {
         match fs::read_to_string(path) {
-            Ok(json) => Config::parse_config(&json),
+            Ok(json) => Config::parse_config(&json).map(|values| (json, values)),
             Err(_) => Err(ConfigError::CannotLoad) 
         }
     }
 
-    fn parse_config(json: &str) -> Result<Self, ConfigError>{
+    fn parse_config(json: &str) -> Result<Value, ConfigError>{
         match serde_json::from_str(json) {
             Ok(config) => Ok(config),
             Err(e) => Err(ConfigError::ParseError(e))
         }
     }
 
-    fn get_config_path() -> String {
-        Config::get_mpv_home() + "rpc.json"
+    pub fn get_cover_art_cache_path(&self) -> Option<String> {
+        match self.cover_art_cache {
+            true => Some(self.paths.cover_art_cache.clone()),
+            false => None
+        }
+    }
+
+    // MPV_RPC_TRACE allows tracing a single session without editing the config
+    pub fn get_trace_path(&self) -> Option<String> {
+        match env::var("MPV_RPC_TRACE") {
+            Ok(path) if !path.is_empty() => Some(path),
+            _ => self.trace.clone()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            active: active_default(),
+            application_id: application_id_default(),
+            assets: AssetsConfig::default(),
+            cover_art: cover_art_default(),
+            timestamps: timestamps_default(),
+            cover_art_providers: cover_art_providers_default(),
+            cover_art_cache: cover_art_cache_default(),
+            buttons: ButtonsConfig::default(),
+            templates: TemplatesConfig::default(),
+            activity_types: ActivityTypesConfig::default(),
+            privacy: PrivacyConfig::default(),
+            trace: None,
+            paths: ConfigPaths::default()
+        }
     }
+}
 
-    fn get_mpv_home() -> String {
-        if let Ok(home) = env::var("MPV_HOME") {
-            return home;
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            large_image: large_image_default(),
+            large_text: large_text_default(),
+            playing_image: None,
+            paused_image: None
         }
+    }
+}
 
-        if let Ok(home) = env::var("XDG_CONFIG_HOME") {
-            return home + "/mpv/";
+impl Default for ButtonsConfig {
+    fn default() -> Self {
+        Self {
+            release: release_button_default(),
+            source: source_button_default()
         }
+    }
+}
 
-        if let Ok(home) = env::var("HOME") {
-            return home + "/.config/mpv/";
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        Self {
+            details: details_template_default(),
+            state: state_template_default(),
+            large_text: large_text_template_default()
         }
+    }
+}
 
-        "/etc/mpv/".to_owned()
+impl Default for ActivityTypesConfig {
+    fn default() -> Self {
+        Self {
+            audio: audio_activity_type_default(),
+            video: video_activity_type_default()
+        }
     }
 }
 
-impl Default for Config {
+impl Default for PrivacyConfig {
     fn default() -> Self {
         Self {
-            active: active_default(),
-            cover_art: cover_art_default()
+            action: privacy_action_default(),
+            blocklist: Vec::new()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::logging::LogLevel;
+    use crate::test_utils::TempDir;
+    use super::*;
+
+    fn get_paths(dir: &TempDir) -> ConfigPaths {
+        ConfigPaths {
+            configs: vec![dir.file("global.json"), dir.file("rpc.json")],
+            script_opts: dir.file("rpc.conf"),
+            cover_art_cache: dir.file("cache.json")
+        }
+    }
+
+    fn load(paths: &ConfigPaths, script_opts: Option<&str>) -> (Config, Vec<String>) {
+        Config::from_config_file(paths, script_opts, &Logger::new(LogLevel::None))
+    }
+
+    #[test]
+    fn sources_are_layered() {
+        let dir = TempDir::new("config");
+        let paths = get_paths(&dir);
+        fs::write(dir.file("global.json"), r#"{"timestamps": "elapsed", "templates": {"details": "global"}}"#).unwrap();
+        fs::write(dir.file("rpc.json"), r#"{"active": false, "templates": {"details": "json", "state": "json"}}"#).unwrap();
+        fs::write(dir.file("rpc.conf"), "active=yes\ntemplates-details=conf\ncover_art=no\n").unwrap();
+
+        let (config, issues) = load(&paths, Some("rpc-templates-details=opts,rpc-cover_art=yes"));
+        assert!(issues.is_empty(), "{issues:?}");
+        assert!(matches!(config.timestamps, TimestampsMode::Elapsed));
+        assert!(config.active);
+        assert!(config.cover_art);
+        assert_eq!(config.templates.details, "opts");
+        assert_eq!(config.templates.state, "json");
+        assert_eq!(config.templates.large_text, LARGE_TEXT_TEMPLATE);
+    }
+
+    #[test]
+    fn unknown_options_are_reported() {
+        let dir = TempDir::new("config");
+        let paths = get_paths(&dir);
+        fs::write(dir.file("rpc.conf"), "colour=red\nactive=maybe\n").unwrap();
+
+        let (config, issues) = load(&paths, Some("rpc-cover_art=no,rpc-templates-detail=x"));
+        assert!(!config.active);
+        assert!(!config.cover_art);
+        assert_eq!(issues, vec![
+            format!("{}: invalid value for \"active\": expected yes or no", dir.file("rpc.conf")),
+            format!("{}: unknown key \"colour\"", dir.file("rpc.conf")),
+            "--script-opts: unknown key \"templates.detail\", did you mean \"templates.details\"?".to_string()
+        ]);
+    }
+
+    #[test]
+    fn broken_config_file_is_skipped() {
+        let dir = TempDir::new("config");
+        let paths = get_paths(&dir);
+        fs::write(dir.file("global.json"), r#"{"timestamps": "elapsed", "cover_art_cache": false}"#).unwrap();
+        fs::write(dir.file("rpc.json"), "{\n    \"cover_art\": true,\n}").unwrap();
+        fs::write(dir.file("rpc.conf"), "active=yes\n").unwrap();
+
+        let (config, issues) = load(&paths, Some("rpc-cover_art=no"));
+        assert!(config.active);
+        assert!(!config.cover_art);
+        assert!(!config.cover_art_cache);
+        assert!(matches!(config.timestamps, TimestampsMode::Elapsed));
+
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with(&format!("{}: trailing comma", dir.file("rpc.json"))), "{}", issues[0]);
+    }
+
+    #[test]
+    fn broken_config_file_fails_reload() {
+        let dir = TempDir::new("config");
+        let paths = get_paths(&dir);
+        fs::write(dir.file("rpc.json"), "{\"active\": }").unwrap();
+        fs::write(dir.file("rpc.conf"), "active=yes\n").unwrap();
+
+        let reloaded = Config::reload_config_file(&paths, None, &Logger::new(LogLevel::None));
+        assert!(reloaded.is_err_and(|e| e.starts_with(&dir.file("rpc.json"))));
+    }
+}
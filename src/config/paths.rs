@@ -0,0 +1,159 @@
+use std::env;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+use mpv_client::mpv_handle;
+
+// mpv-client only binds mpv_command, which doesn't return the result of the command
+#[allow(non_camel_case_types)]
+#[repr(C)]
+union mpv_node_value {
+    string: *mut c_char,
+    flag: c_int,
+    int64: i64,
+    double: f64,
+    list: *mut c_void,
+    ba: *mut c_void
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct mpv_node {
+    u: mpv_node_value,
+    format: c_int
+}
+
+const MPV_FORMAT_NONE: c_int = 0;
+const MPV_FORMAT_STRING: c_int = 1;
+
+extern "C" {
+    fn mpv_command_ret(ctx: *mut mpv_handle, args: *const *const c_char, result: *mut mpv_node) -> c_int;
+    fn mpv_free_node_contents(node: *mut mpv_node);
+}
+
+// Where the config is read from, as resolved by mpv, so --config-dir
+// and portable configs are respected
+#[derive(Clone)]
+pub struct ConfigPaths {
+    // System-wide config first, the user config overrides its values
+    pub configs: Vec<String>,
+    pub script_opts: String,
+    pub cover_art_cache: String
+}
+
+impl ConfigPaths {
+    pub fn from_mpv(handle: *mut mpv_handle) -> Self {
+        let home = ConfigPaths::expand_path(handle, "~~home/").unwrap_or_else(ConfigPaths::get_mpv_home);
+        let global = ConfigPaths::expand_path(handle, "~~global/");
+
+        ConfigPaths::new(&home, global.as_deref())
+    }
+
+    // Guesses the directories the way mpv does, when it can't be asked
+    pub fn from_env() -> Self {
+        ConfigPaths::new(&ConfigPaths::get_mpv_home(), Some("/etc/mpv"))
+    }
+
+    fn new(home: &str, global: Option<&str>) -> Self {
+        let home = home.trim_end_matches('/');
+        let mut configs: Vec<String> = global.map(|global| format!("{}/rpc.json", global.trim_end_matches('/')))
+                                             .into_iter()
+                                             .collect();
+
+        let user_config = format!("{home}/rpc.json");
+        if !configs.contains(&user_config) {
+            configs.push(user_config);
+        }
+
+        Self {
+            configs,
+            script_opts: format!("{home}/script-opts/rpc.conf"),
+            cover_art_cache: format!("{home}/rpc_cover_art_cache.json")
+        }
+    }
+
+    // Files to watch for changes
+    pub fn get_watched(&self) -> Vec<String> {
+        let mut paths = self.configs.clone();
+        paths.push(self.script_opts.clone());
+        paths
+    }
+
+    // Unavailable prefixes, like ~~global/ on some platforms, aren't expanded
+    fn expand_path(handle: *mut mpv_handle, path: &str) -> Option<String> {
+        let command = CString::new("expand-path").ok()?;
+        let path = CString::new(path).ok()?;
+        let args = [command.as_ptr(), path.as_ptr(), ptr::null()];
+
+        let mut node = mpv_node {
+            u: mpv_node_value { int64: 0 },
+            format: MPV_FORMAT_NONE
+        };
+
+        let expanded = unsafe {
+            if mpv_command_ret(handle, args.as_ptr(), &mut node) < 0 {
+                return None;
+            }
+
+            let expanded = match node.format {
+                MPV_FORMAT_STRING => Some(CStr::from_ptr(node.u.string).to_string_lossy().into_owned()),
+                _ => None
+            };
+
+            mpv_free_node_contents(&mut node);
+            expanded
+        };
+
+        expanded.filter(|expanded| !expanded.is_empty() && !expanded.starts_with("~~"))
+    }
+
+    fn get_mpv_home() -> String {
+        if let Ok(home) = env::var("MPV_HOME") {
+            return home;
+        }
+
+        if let Ok(home) = env::var("XDG_CONFIG_HOME") {
+            return home + "/mpv";
+        }
+
+        if let Ok(home) = env::var("HOME") {
+            return home + "/.config/mpv";
+        }
+
+        "/etc/mpv".to_owned()
+    }
+}
+
+impl Default for ConfigPaths {
+    fn default() -> Self {
+        ConfigPaths::from_env()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_in_config_dirs() {
+        let paths = ConfigPaths::new("/home/user/.config/mpv/", Some("/etc/mpv/"));
+
+        assert_eq!(paths.configs, vec!["/etc/mpv/rpc.json", "/home/user/.config/mpv/rpc.json"]);
+        assert_eq!(paths.script_opts, "/home/user/.config/mpv/script-opts/rpc.conf");
+        assert_eq!(paths.cover_art_cache, "/home/user/.config/mpv/rpc_cover_art_cache.json");
+        assert_eq!(paths.get_watched(), vec![
+            "/etc/mpv/rpc.json",
+            "/home/user/.config/mpv/rpc.json",
+            "/home/user/.config/mpv/script-opts/rpc.conf"
+        ]);
+    }
+
+    #[test]
+    fn config_is_read_once() {
+        // Portable configs have no global config, or the same directory for both
+        let paths = ConfigPaths::new("/opt/mpv/portable_config", None);
+        assert_eq!(paths.configs, vec!["/opt/mpv/portable_config/rpc.json"]);
+
+        let paths = ConfigPaths::new("/opt/mpv/portable_config/", Some("/opt/mpv/portable_config"));
+        assert_eq!(paths.configs, vec!["/opt/mpv/portable_config/rpc.json"]);
+    }
+}
@@ -0,0 +1,209 @@
+use serde_json::{Map, Value};
+
+// Options of the plugin in --script-opts are prefixed with the script name
+const SCRIPT_OPTS_PREFIX: &str = "rpc-";
+
+// Nested keys are joined with dashes, like templates-details
+const KEY_SEPARATOR: char = '-';
+
+// key=value lines of script-opts/rpc.conf, without the prefix.
+// Lines starting with # are comments
+pub fn parse_conf(conf: &str, name: &str, issues: &mut Vec<String>) -> Vec<(String, String)> {
+    conf.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|(number, line)| match line.split_once('=') {
+            Some((key, value)) => Some((key.trim().to_string(), value.to_string())),
+            None => {
+                issues.push(format!("{name}:{number}: line without a value"));
+                None
+            }
+        })
+        .collect()
+}
+
+// The script-opts property as mpv prints it, like "rpc-active=yes,osc-layout=box".
+// Entries without a value continue the list of the previous one,
+// so "rpc-cover_art_providers=itunes,musicbrainz" keeps both providers
+pub fn parse_script_opts(script_opts: &str) -> Vec<(String, String)> {
+    let mut options: Vec<(String, String)> = Vec::new();
+
+    for entry in script_opts.split(',').filter(|entry| !entry.is_empty()) {
+        match entry.split_once('=') {
+            Some((key, value)) => options.push((key.to_string(), value.to_string())),
+            None => {
+                if let Some((_, value)) = options.last_mut() {
+                    value.push(',');
+                    value.push_str(entry);
+                }
+            }
+        }
+    }
+
+    options.into_iter()
+           .filter_map(|(key, value)| Some((key.strip_prefix(SCRIPT_OPTS_PREFIX)?.to_string(), value)))
+           .collect()
+}
+
+// Sets the options in the JSON config, converting the values to the types of the defaults.
+// Unknown keys are left for validation to report, invalid values are reported and skipped
+pub fn apply(config: &mut Value, defaults: &Value, options: &[(String, String)], name: &str, issues: &mut Vec<String>) {
+    for (key, value) in options {
+        let path: Vec<&str> = key.split(KEY_SEPARATOR).collect();
+        let default = path.iter().try_fold(defaults, |default, key| default.get(key));
+
+        let value = match default.map(|default| convert_value(default, value)) {
+            Some(Ok(value)) => value,
+            Some(Err(e)) => {
+                issues.push(format!("{name}: invalid value for \"{key}\": {e}"));
+                continue;
+            }
+            None => Value::String(value.to_string())
+        };
+
+        set_value(config, &path, value);
+    }
+}
+
+fn convert_value(default: &Value, value: &str) -> Result<Value, &'static str> {
+    match default {
+        Value::Bool(_) => match value {
+            "yes" | "true" => Ok(Value::Bool(true)),
+            "no" | "false" => Ok(Value::Bool(false)),
+            _ => Err("expected yes or no")
+        },
+        Value::Array(items) if items.iter().all(Value::is_string) && !items.is_empty() => {
+            let items = value.split(',')
+                             .map(str::trim)
+                             .filter(|item| !item.is_empty())
+                             .map(|item| Value::String(item.to_string()))
+                             .collect();
+            Ok(Value::Array(items))
+        }
+        Value::String(_) | Value::Null => Ok(Value::String(value.to_string())),
+        Value::Object(_) => Err("expected a nested key"),
+        _ => Err("can only be set in rpc.json")
+    }
+}
+
+// Objects on the way are created, replacing values of other types
+fn set_value(config: &mut Value, path: &[&str], value: Value) {
+    if !config.is_object() {
+        *config = Value::Object(Map::new());
+    }
+
+    let map = match config {
+        Value::Object(map) => map,
+        _ => return
+    };
+
+    match path {
+        [key] => {
+            map.insert(key.to_string(), value);
+        }
+        [parent, path @ ..] => {
+            let object = map.entry(parent.to_string()).or_insert(Value::Null);
+            set_value(object, path, value);
+        }
+        [] => ()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn options(options: &[(&str, &str)]) -> Vec<(String, String)> {
+        options.iter()
+               .map(|(key, value)| (key.to_string(), value.to_string()))
+               .collect()
+    }
+
+    fn apply_options(defaults: &Value, options: &[(String, String)]) -> (Value, Vec<String>) {
+        let mut config = json!({});
+        let mut issues = Vec::new();
+        apply(&mut config, defaults, options, "rpc.conf", &mut issues);
+
+        (config, issues)
+    }
+
+    #[test]
+    fn conf_lines() {
+        let conf = "# Comment\n\nactive=yes\n  templates-details = {title}  \ncover_art\n";
+        let mut issues = Vec::new();
+
+        let parsed = parse_conf(conf, "rpc.conf", &mut issues);
+        assert_eq!(parsed, options(&[("active", "yes"), ("templates-details", " {title}")]));
+        assert_eq!(issues, vec!["rpc.conf:5: line without a value"]);
+    }
+
+    #[test]
+    fn script_opts_of_plugin() {
+        let parsed = parse_script_opts("osc-layout=box,rpc-active=yes,rpc-cover_art_providers=itunes,musicbrainz,ytdl_hook-try_ytdl_first=yes,rpc-templates-state=");
+        assert_eq!(parsed, options(&[
+            ("active", "yes"),
+            ("cover_art_providers", "itunes,musicbrainz"),
+            ("templates-state", "")
+        ]));
+
+        assert!(parse_script_opts("").is_empty());
+        assert!(parse_script_opts("rpc,rpcactive=yes,other-rpc-active=yes").is_empty());
+    }
+
+    #[test]
+    fn values_are_converted() {
+        let defaults = json!({
+            "active": false,
+            "cover_art": true,
+            "providers": ["musicbrainz"],
+            "trace": null,
+            "templates": { "details": "{title}" }
+        });
+
+        let (config, issues) = apply_options(&defaults, &options(&[
+            ("active", "yes"),
+            ("cover_art", "false"),
+            ("providers", " itunes, ,musicbrainz "),
+            ("trace", "/tmp/trace.jsonl"),
+            ("templates-details", "{artist} - {title}")
+        ]));
+        assert!(issues.is_empty(), "{issues:?}");
+        assert_eq!(config, json!({
+            "active": true,
+            "cover_art": false,
+            "providers": ["itunes", "musicbrainz"],
+            "trace": "/tmp/trace.jsonl",
+            "templates": { "details": "{artist} - {title}" }
+        }));
+    }
+
+    #[test]
+    fn invalid_values_are_skipped() {
+        let defaults = json!({ "active": false, "retries": 3, "templates": { "details": "{title}" } });
+
+        let (config, issues) = apply_options(&defaults, &options(&[
+            ("active", "1"),
+            ("retries", "5"),
+            ("templates", "{title}"),
+            ("templates-details", "{album}")
+        ]));
+        assert_eq!(issues, vec![
+            "rpc.conf: invalid value for \"active\": expected yes or no",
+            "rpc.conf: invalid value for \"retries\": can only be set in rpc.json",
+            "rpc.conf: invalid value for \"templates\": expected a nested key"
+        ]);
+        assert_eq!(config, json!({ "templates": { "details": "{album}" } }));
+    }
+
+    // Validation reports them along with the unknown keys of rpc.json
+    #[test]
+    fn unknown_options_are_kept_as_strings() {
+        let defaults = json!({ "active": false, "templates": { "details": "{title}" } });
+
+        let (config, issues) = apply_options(&defaults, &options(&[("colour", "red"), ("templates-extra", "x")]));
+        assert!(issues.is_empty());
+        assert_eq!(config, json!({ "colour": "red", "templates": { "extra": "x" } }));
+    }
+}
@@ -0,0 +1,408 @@
+use std::iter::Peekable;
+use std::marker::PhantomData;
+use std::str::Chars;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+// Checks the values of a config source one field at a time against the defaults,
+// so a single bad field doesn't discard the others.
+// Unknown keys and invalid values are removed and described in the returned messages,
+// with their line and column if the JSON source is given
+pub fn validate<T: DeserializeOwned>(values: &mut Value, defaults: &Value, name: &str, source: Option<&str>) -> Vec<String> {
+    let mut validator = Validator::<T> {
+        defaults,
+        name,
+        source,
+        issues: Vec::new(),
+        config: PhantomData
+    };
+
+    match (values, defaults) {
+        (Value::Object(values), Value::Object(defaults)) => validator.validate_object(values, defaults, &mut Vec::new()),
+        (values, _) => {
+            validator.issues.push(format!("{name}: config must be a JSON object"));
+            *values = Value::Object(Map::new());
+        }
+    }
+
+    validator.issues
+}
+
+struct Validator<'a, T> {
+    defaults: &'a Value,
+    name: &'a str,
+    source: Option<&'a str>,
+    issues: Vec<String>,
+    config: PhantomData<T>
+}
+
+impl<T: DeserializeOwned> Validator<'_, T> {
+    fn validate_object(&mut self, values: &mut Map<String, Value>, defaults: &Map<String, Value>, path: &mut Vec<String>) {
+        let keys: Vec<String> = values.keys().cloned().collect();
+        for key in keys {
+            path.push(key.clone());
+
+            match (values.get_mut(&key), defaults.get(&key)) {
+                (_, None) => {
+                    let message = match Validator::<T>::get_closest_key(&key, defaults) {
+                        Some(closest) => {
+                            let parent = &path[..path.len() - 1];
+                            let closest: Vec<&str> = parent.iter().map(String::as_str).chain([closest]).collect();
+                            format!("unknown key \"{}\", did you mean \"{}\"?", path.join("."), closest.join("."))
+                        }
+                        None => format!("unknown key \"{}\"", path.join("."))
+                    };
+                    self.report(path, message);
+                    values.remove(&key);
+                }
+                (Some(Value::Object(nested)), Some(Value::Object(nested_defaults))) => {
+                    self.validate_object(nested, nested_defaults, path);
+                }
+                (Some(Value::Array(items)), Some(Value::Array(_))) => {
+                    self.validate_array(items, path);
+                }
+                (Some(value), Some(_)) => {
+                    if let Err(e) = self.check_value(path, value.clone()) {
+                        self.report(path, format!("invalid value for \"{}\": {e}", path.join(".")));
+                        values.remove(&key);
+                    }
+                }
+                (None, Some(_)) => ()
+            }
+
+            path.pop();
+        }
+    }
+
+    // Lists are checked item by item, so one bad rule doesn't drop the whole blocklist
+    fn validate_array(&mut self, items: &mut Vec<Value>, path: &[String]) {
+        let mut index = 0;
+        items.retain(|item| {
+            let result = self.check_value(path, Value::Array(vec![item.clone()]));
+            if let Err(e) = &result {
+                self.report(path, format!("invalid item {index} of \"{}\": {e}", path.join(".")));
+            }
+
+            index += 1;
+            result.is_ok()
+        });
+    }
+
+    // The value is tried in place of the default, all other fields stay valid
+    fn check_value(&self, path: &[String], value: Value) -> Result<(), serde_json::Error> {
+        let mut config = self.defaults.clone();
+        let field = path.iter().try_fold(&mut config, |config, key| config.get_mut(key));
+        if let Some(field) = field {
+            *field = value;
+        }
+
+        serde_json::from_value::<T>(config).map(|_| ())
+    }
+
+    fn report(&mut self, path: &[String], message: String) {
+        let position = self.source.and_then(|source| find_key(source, path));
+        let issue = match position {
+            Some((line, column)) => format!("{}:{line}:{column}: {message}", self.name),
+            None => format!("{}: {message}", self.name)
+        };
+
+        self.issues.push(issue);
+    }
+
+    // Only close matches are suggested, like cover_art for coverart
+    fn get_closest_key<'a>(key: &str, defaults: &'a Map<String, Value>) -> Option<&'a str> {
+        let max_distance = (key.chars().count() / 3).max(2);
+        defaults.keys()
+                .map(|known| (known.as_str(), edit_distance(key, known)))
+                .filter(|(_, distance)| *distance <= max_distance)
+                .min_by_key(|(_, distance)| *distance)
+                .map(|(known, _)| known)
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Line and column of a key in JSON source, which is known to be valid.
+// serde_json doesn't keep positions in parsed values
+fn find_key(source: &str, path: &[String]) -> Option<(usize, usize)> {
+    let mut scanner = Scanner {
+        chars: source.chars().peekable(),
+        line: 1,
+        column: 1
+    };
+
+    scanner.find_in_value(path, &mut Vec::new())
+}
+
+struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize
+}
+
+impl Scanner<'_> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1
+        }
+
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn find_in_value(&mut self, target: &[String], path: &mut Vec<String>) -> Option<(usize, usize)> {
+        self.skip_whitespace();
+        match self.chars.peek()? {
+            '{' => self.find_in_object(target, path),
+            '[' => {
+                self.skip_array();
+                None
+            }
+            '"' => {
+                self.read_string();
+                None
+            }
+            _ => {
+                while self.chars.peek().is_some_and(|c| !matches!(c, ',' | '}' | ']') && !c.is_whitespace()) {
+                    self.next();
+                }
+                None
+            }
+        }
+    }
+
+    fn find_in_object(&mut self, target: &[String], path: &mut Vec<String>) -> Option<(usize, usize)> {
+        self.next();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek()? {
+                '}' => {
+                    self.next();
+                    return None;
+                }
+                ',' => {
+                    self.next();
+                    continue;
+                }
+                _ => ()
+            }
+
+            let position = (self.line, self.column);
+            let key = self.read_string();
+            self.skip_whitespace();
+            self.next();
+
+            path.push(key);
+            if path.as_slice() == target {
+                return Some(position);
+            }
+
+            let found = self.find_in_value(target, path);
+            path.pop();
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+
+    // Keys inside of lists aren't looked up
+    fn skip_array(&mut self) {
+        self.next();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                None => return,
+                Some(']') => {
+                    self.next();
+                    return;
+                }
+                Some(',') => {
+                    self.next();
+                }
+                Some(_) => {
+                    self.find_in_value(&[], &mut Vec::new());
+                }
+            }
+        }
+    }
+
+    // Escapes other than \" and \\ are kept as they are, config keys don't use them
+    fn read_string(&mut self) -> String {
+        let mut text = String::new();
+        self.next();
+
+        while let Some(c) = self.next() {
+            match c {
+                '"' => break,
+                '\\' => {
+                    if let Some(escaped) = self.next() {
+                        text.push(escaped);
+                    }
+                }
+                c => text.push(c)
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::config::Config;
+    use super::*;
+
+    fn validate_config(values: &mut Value, source: Option<&str>) -> Vec<String> {
+        let defaults = serde_json::to_value(Config::default()).unwrap();
+        validate::<Config>(values, &defaults, "rpc.json", source)
+    }
+
+    fn get_closest_key(key: &str) -> Option<String> {
+        let Value::Object(defaults) = json!({ "active": true, "cover_art": true, "timestamps": "remaining" }) else {
+            unreachable!();
+        };
+
+        Validator::<Config>::get_closest_key(key, &defaults).map(str::to_string)
+    }
+
+    #[test]
+    fn unknown_keys_are_removed() {
+        let mut values = json!({
+            "activ": true,
+            "templates": { "detials": "{title}", "state": "{artist}" },
+            "something": 1
+        });
+
+        let mut issues = validate_config(&mut values, None);
+        issues.sort();
+        assert_eq!(issues, vec![
+            "rpc.json: unknown key \"activ\", did you mean \"active\"?",
+            "rpc.json: unknown key \"something\"",
+            "rpc.json: unknown key \"templates.detials\", did you mean \"templates.details\"?"
+        ]);
+        assert_eq!(values, json!({ "templates": { "state": "{artist}" } }));
+    }
+
+    #[test]
+    fn only_invalid_values_are_removed() {
+        let mut values = json!({
+            "active": true,
+            "timestamps": "sometimes",
+            "assets": { "large_image": "", "large_text": "Player" }
+        });
+
+        let issues = validate_config(&mut values, None);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].starts_with("rpc.json: invalid value for \"assets.large_image\": value can't be empty"), "{}", issues[0]);
+        assert!(issues[1].starts_with("rpc.json: invalid value for \"timestamps\": unknown variant `sometimes`"), "{}", issues[1]);
+        assert_eq!(values, json!({ "active": true, "assets": { "large_text": "Player" } }));
+    }
+
+    #[test]
+    fn only_invalid_items_are_removed() {
+        let mut values = json!({
+            "privacy": {
+                "blocklist": [{ "path": "~/Private" }, { "folder": "~/Secret" }, { "host": "example.com" }]
+            }
+        });
+
+        let issues = validate_config(&mut values, None);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("rpc.json: invalid item 1 of \"privacy.blocklist\": unknown variant `folder`"), "{}", issues[0]);
+        assert_eq!(values, json!({
+            "privacy": { "blocklist": [{ "path": "~/Private" }, { "host": "example.com" }] }
+        }));
+    }
+
+    #[test]
+    fn root_must_be_object() {
+        let mut values = json!(["active"]);
+
+        let issues = validate_config(&mut values, None);
+        assert_eq!(issues, vec!["rpc.json: config must be a JSON object"]);
+        assert_eq!(values, json!({}));
+    }
+
+    #[test]
+    fn issues_have_positions() {
+        let source = r#"{
+    "cover_art_providers": ["itunes", "music\"brainz]"],
+    "templates": {
+        "details": "{title} \"quoted\" \\",
+        "stat": "x"
+    },
+    "privacy": { "blocklist": [{ "path": "a" }, { "nope": 1 }] },
+    "activ": true
+}"#;
+        let mut values = serde_json::from_str(source).unwrap();
+
+        let mut issues = validate_config(&mut values, Some(source));
+        issues.sort();
+        assert_eq!(issues.len(), 4);
+        assert!(issues[0].starts_with("rpc.json:2:5: invalid item 1 of \"cover_art_providers\""), "{}", issues[0]);
+        assert_eq!(issues[1], "rpc.json:5:9: unknown key \"templates.stat\", did you mean \"templates.state\"?");
+        assert!(issues[2].starts_with("rpc.json:7:18: invalid item 1 of \"privacy.blocklist\""), "{}", issues[2]);
+        assert_eq!(issues[3], "rpc.json:8:5: unknown key \"activ\", did you mean \"active\"?");
+    }
+
+    #[test]
+    fn keys_are_found_after_nested_values() {
+        let source = "{\"a\": [[1, {\"b\": 2}], \"]\"],\n  \"c\": {\"d\": \"\\\\\"},\n\t\"e\": {\n\"f\": null}}";
+        let path = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(find_key(source, &path(&["a"])), Some((1, 2)));
+        assert_eq!(find_key(source, &path(&["c"])), Some((2, 3)));
+        assert_eq!(find_key(source, &path(&["c", "d"])), Some((2, 9)));
+        assert_eq!(find_key(source, &path(&["e"])), Some((3, 2)));
+        assert_eq!(find_key(source, &path(&["e", "f"])), Some((4, 1)));
+
+        // Keys in lists aren't looked up
+        assert_eq!(find_key(source, &path(&["b"])), None);
+        assert_eq!(find_key(source, &path(&["x"])), None);
+    }
+
+    #[test]
+    fn close_keys_are_suggested() {
+        assert_eq!(get_closest_key("activ").as_deref(), Some("active"));
+        assert_eq!(get_closest_key("coverart").as_deref(), Some("cover_art"));
+        assert_eq!(get_closest_key("Cover-Art").as_deref(), Some("cover_art"));
+        assert_eq!(get_closest_key("timestmp").as_deref(), Some("timestamps"));
+        assert_eq!(get_closest_key("time"), None);
+        assert_eq!(get_closest_key("xyz"), None);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("active", "active"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("activ", "active"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("cover_art", "coverart"), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+// The files are checked at most this often, the event loop wakes up more frequently
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Notices changes to the config files by their modification time
+pub struct ConfigWatcher {
+    paths: Vec<String>,
+    modified: Vec<Option<SystemTime>>,
+    next_check: Instant
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<String>) -> Self {
+        let modified = ConfigWatcher::get_modified(&paths);
+        Self {
+            paths,
+            modified,
+            next_check: Instant::now() + CHECK_INTERVAL
+        }
+    }
+
+    // Creating and deleting the files count as changes as well
+    pub fn has_changed(&mut self) -> bool {
+        if Instant::now() < self.next_check {
+            return false;
+        }
+
+        self.next_check = Instant::now() + CHECK_INTERVAL;
+        self.check_files()
+    }
+
+    fn check_files(&mut self) -> bool {
+        let modified = ConfigWatcher::get_modified(&self.paths);
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        true
+    }
+
+    fn get_modified(paths: &[String]) -> Vec<Option<SystemTime>> {
+        paths.iter()
+             .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+             .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use crate::test_utils::TempDir;
+    use super::*;
+
+    fn set_modified(path: &str, modified: SystemTime) {
+        File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reloaded() {
+        let dir = TempDir::new("watcher");
+        fs::write(dir.file("rpc.json"), "{}").unwrap();
+        let mut watcher = ConfigWatcher::new(vec![dir.file("rpc.json"), dir.file("rpc.conf")]);
+
+        assert!(!watcher.check_files());
+        assert!(!watcher.check_files());
+    }
+
+    #[test]
+    fn changes_are_noticed_once() {
+        let dir = TempDir::new("watcher");
+        let path = dir.file("rpc.json");
+        let mut watcher = ConfigWatcher::new(vec![dir.file("rpc.conf"), path.clone()]);
+
+        // Created
+        fs::write(&path, "{}").unwrap();
+        set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(1000));
+        assert!(watcher.check_files());
+        assert!(!watcher.check_files());
+
+        // Modified
+        set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(2000));
+        assert!(watcher.check_files());
+        assert!(!watcher.check_files());
+
+        // Deleted
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.check_files());
+        assert!(!watcher.check_files());
+    }
+
+    #[test]
+    fn files_are_checked_after_interval() {
+        let dir = TempDir::new("watcher");
+        let path = dir.file("rpc.json");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+
+        fs::write(&path, "{}").unwrap();
+        assert!(!watcher.has_changed());
+
+        watcher.next_check = Instant::now();
+        assert!(watcher.has_changed());
+        assert!(watcher.next_check > Instant::now());
+    }
+}
@@ -1,28 +1,54 @@
 use std::rc::Rc;
+use std::path::PathBuf;
 use std::time::SystemTime;
 use std::collections::VecDeque;
-use discord_rich_presence::{DiscordIpcClient, DiscordIpc};
-use discord_rich_presence::activity::{Activity, Assets, Timestamps};
+use discord_rich_presence::DiscordIpc;
+use discord_rich_presence::activity::{Activity, ActivityType, Assets, Button, Timestamps};
 use crate::utils;
+use crate::config::{Config, AssetsConfig, ButtonsConfig, TimestampsMode, ActivityTypesConfig, ActivityTypeName, PrivacyAction};
 use crate::logging::{self, Logger};
-use crate::mpv_event_queue::events::{MpvEventHandler, MpvEvent, FileInfo, MpvRequester, MpvRequest, FileMetadata};
+use crate::mpv_event_queue::commands::Command;
+use crate::mpv_event_queue::events::{MpvEventHandler, MpvEvent, FileInfo, MpvRequester, MpvRequest, FileMetadata, PlaybackTime, MediaKind};
 
 const MAX_STR_LEN: usize = 128;
 
-mod music_brainz;
+mod cover_art_provider;
+mod cover_art_worker;
+mod cover_art_cache;
+mod buttons;
+mod presence_templates;
+mod backoff;
+mod connection_state;
+mod privacy;
+mod ipc_client;
+
+#[cfg(test)]
+pub mod fake_discord;
+
+use cover_art_worker::{CoverArtWorker, CoverArtRequest, CoverArtResult};
+use cover_art_cache::CoverArtCache;
+use buttons::ButtonInfo;
+use presence_templates::PresenceTemplates;
+use connection_state::{ConnectionState, ConnectionEvent};
+use privacy::Privacy;
+use ipc_client::IpcClient;
 
 struct ActivityInfo {
+    activity_type: ActivityType,
     details: String,
     state: String,
     assets: AssetsInfo,
-    timestamps: Timestamps
+    timestamps: Timestamps,
+    release_button: Option<ButtonInfo>,
+    source_button: Option<ButtonInfo>
 }
 
 impl AssetsInfo {
-    pub fn new(large_image: String, large_text: String) -> Self {
+    pub fn new(large_image: String, large_text: String, small_image: Option<String>) -> Self {
         Self {
             large_image,
-            large_text
+            large_text,
+            small_image
         }
     }
 
@@ -30,97 +56,208 @@ impl AssetsInfo {
         Self {
             large_image: String::new(),
             large_text: String::new(),
+            small_image: None
         }
     }
 
-    pub fn get_assets(&self) -> Assets {
-        Assets::new()
+    pub fn get_assets(&self) -> Assets<'_> {
+        let assets = Assets::new()
                     .large_image(&self.large_image)
-                    .large_text(&self.large_text)
+                    .large_text(&self.large_text);
+
+        match self.small_image {
+            Some(ref small_image) => assets.small_image(small_image),
+            None => assets
+        }
     }
 }
 
 struct AssetsInfo {
     large_image: String,
-    large_text: String
+    large_text: String,
+    small_image: Option<String>
 }
 
 impl ActivityInfo {
-    pub fn new(details: String, state: String, assets: AssetsInfo, timestamps: Timestamps) -> Self {
+    pub fn new(activity_type: ActivityType, details: String, state: String, assets: AssetsInfo, timestamps: Timestamps) -> Self {
         Self {
+            activity_type,
             details,
             state,
             assets,
-            timestamps
+            timestamps,
+            release_button: None,
+            source_button: None
         }
     }
 
     pub fn empty() -> Self {
         Self {
+            activity_type: ActivityType::Playing,
             details: String::new(),
             state: String::new(),
             assets: AssetsInfo::empty(),
-            timestamps: Timestamps::new()
+            timestamps: Timestamps::new(),
+            release_button: None,
+            source_button: None
         }
     }
 
 
-    pub fn get_activity(&self) -> Activity {
+    pub fn get_activity(&self) -> Activity<'_> {
         let assets = self.assets.get_assets();
-
-        Activity::new()
+        let activity = Activity::new()
+                    .activity_type(self.activity_type.clone())
                     .assets(assets)
                     .details(&self.details)
                     .state(&self.state)
-                    .timestamps(self.timestamps.clone())
+                    .timestamps(self.timestamps.clone());
+
+        // Discord doesn't accept an empty list of buttons
+        let buttons = self.get_buttons();
+        match buttons.is_empty() {
+            true => activity,
+            false => activity.buttons(buttons)
+        }
+    }
+
+    fn get_buttons(&self) -> Vec<Button<'_>> {
+        [&self.release_button, &self.source_button].into_iter()
+                                                   .flatten()
+                                                   .map(|button| Button::new(&button.label, &button.url))
+                                                   .collect()
     }
 }
 
 
 
 pub struct DiscordClient {
-    discord: DiscordIpcClient,
+    discord: IpcClient,
     activity_info: ActivityInfo,
-    active: bool,
+    file_info: Option<FileInfo>,
+    paused: bool,
+    connection: ConnectionState,
     cover_art: bool,
+    timestamps: TimestampsMode,
+    assets: AssetsConfig,
+    buttons: ButtonsConfig,
+    activity_types: ActivityTypesConfig,
+    templates: PresenceTemplates,
+    privacy: Privacy,
+
+    // Applied to the current file, if it is kept private
+    privacy_action: Option<PrivacyAction>,
+
+    // Text set with commands, kept across files until cleared
+    details_override: Option<String>,
+    state_override: Option<String>,
+    cover_art_worker: CoverArtWorker,
+    cover_art_request: u64,
     mpv_requests: VecDeque<MpvRequest>,
     logger: Rc<Logger>
 }
 
 impl DiscordClient {
-    pub fn new(client_id: &str, active: bool, cover_art: bool, logger: Rc<Logger>) -> Result<Self, &'static str> {
-        let discord = match DiscordIpcClient::new(client_id) {
-            Ok(discord) => discord,
-            Err(_) => return Err("cannot init discord client")
-        };
+    pub fn new(client_id: &str, config: &Config, logger: Rc<Logger>) -> Result<Self, &'static str> {
+        DiscordClient::with_socket(client_id, None, config, logger)
+    }
+
+    // The socket of Discord is searched for, unless one is given
+    pub fn with_socket(client_id: &str, socket_path: Option<PathBuf>, config: &Config, logger: Rc<Logger>) -> Result<Self, &'static str> {
+        let cover_art_providers = cover_art_provider::from_names(&config.cover_art_providers);
+        let cover_art_cache = config.get_cover_art_cache_path().map(CoverArtCache::new);
+        let discord = IpcClient::new(client_id, socket_path);
 
         let mut new_self = Self {
             discord,
             activity_info: ActivityInfo::empty(),
-            active: false,
-            cover_art,
+            file_info: None,
+            paused: false,
+            connection: ConnectionState::Disabled,
+            cover_art: config.cover_art,
+            timestamps: config.timestamps,
+            assets: config.assets.clone(),
+            buttons: config.buttons.clone(),
+            activity_types: config.activity_types,
+            templates: PresenceTemplates::from_config(&config.templates, &logger),
+            privacy: Privacy::from_config(&config.privacy, &logger),
+            privacy_action: None,
+            details_override: None,
+            state_override: None,
+            cover_art_worker: CoverArtWorker::new(cover_art_providers, cover_art_cache)?,
+            cover_art_request: 0,
             mpv_requests: VecDeque::new(),
             logger
         };
 
-        if active {
+        if config.active {
             new_self.open()?;
         }
         Ok(new_self)
     }
 
-    fn get_state(file_info: &FileInfo) -> String {
-        let metadata = &file_info.metadata;
-        let mut state = String::new();
+    // Settings are compared with the previous config, so enabling or disabling RPC
+    // with a key binding isn't undone by reloading an unrelated change
+    pub fn set_config(&mut self, config: &Config, previous: &Config) -> Result<(), &'static str> {
+        // The only setting that can fail, nothing is applied if it does
+        let lookup_changed = config.cover_art_providers != previous.cover_art_providers
+                          || config.cover_art_cache != previous.cover_art_cache;
+        if lookup_changed {
+            let cover_art_providers = cover_art_provider::from_names(&config.cover_art_providers);
+            let cover_art_cache = config.get_cover_art_cache_path().map(CoverArtCache::new);
+            self.cover_art_worker = CoverArtWorker::new(cover_art_providers, cover_art_cache)?;
+        }
+
+        self.cover_art = config.cover_art;
+        self.timestamps = config.timestamps;
+        self.assets = config.assets.clone();
+        self.buttons = config.buttons.clone();
+        self.activity_types = config.activity_types;
+        self.templates = PresenceTemplates::from_config(&config.templates, &self.logger);
+
+        let privacy_enabled = self.privacy.is_enabled();
+        self.privacy = Privacy::from_config(&config.privacy, &self.logger);
+        self.privacy.set_enabled(privacy_enabled);
+
+        // Failing to reach Discord doesn't undo the settings, the connection is retried on its own.
+        // The application is chosen when connecting, a client that failed to disconnect is replaced all the same
+        if config.application_id != previous.application_id {
+            let enabled = self.connection.is_enabled();
+            if let Err(e) = self.close() {
+                logging::error!(self.logger, "Failed to close Discord client: {e}");
+            }
+
+            self.discord = IpcClient::new(&config.application_id, self.discord.get_socket_path());
+            if enabled {
+                if let Err(e) = self.open() {
+                    logging::error!(self.logger, "Failed to open Discord client: {e}");
+                }
+            }
+        }
+
+        if config.active != previous.active {
+            let result = match config.active {
+                true => self.open(),
+                false => self.close()
+            };
 
-        if let Some(artist) = &metadata.artist {
-            state += &format!("by {artist}");
+            if let Err(e) = result {
+                logging::error!(self.logger, "Failed to toggle Discord client: {e}");
+            }
         }
 
-        if let Some(album) = &metadata.album {
-            state += &format!(" on {album}");
+        if let Err(e) = self.refresh_presence() {
+            logging::error!(self.logger, "Failed to update presence: {e}");
         }
 
+        Ok(())
+    }
+
+    fn get_state(&self, file_info: &FileInfo) -> String {
+        let mut state = match self.state_override {
+            Some(ref state) => state.clone(),
+            None => self.templates.get_state(file_info)
+        };
         if state.is_empty() {
             state = String::from("File");
         }
@@ -129,131 +266,445 @@ impl DiscordClient {
         state
     }
 
-    fn get_details(file_info: &FileInfo) -> String {
-        let metadata = &file_info.metadata;
-        let title = match &metadata.title {
-            Some(title) => title,
-            None => return file_info.filename.clone()
+    fn get_details(&self, file_info: &FileInfo) -> String {
+        let mut details = match self.details_override {
+            Some(ref details) => details.clone(),
+            None => self.templates.get_details(file_info)
         };
-
-        let mut details = if let Some(track) = &metadata.track {
-            format!("{title} [T{track}] ")
+        if details.is_empty() {
+            details = file_info.filename.clone();
         }
-        else {
-            title.clone()
-        };
 
         utils::truncate_string_fmt(&mut details, MAX_STR_LEN);
         details
     }
 
-    fn get_assets_info(cover_art: bool, metadata: FileMetadata) -> AssetsInfo {
-        let (large_image, large_text) = DiscordClient::get_large_info(cover_art, metadata);
-        AssetsInfo::new(large_image, large_text)
+    fn get_large_text(&self, file_info: &FileInfo) -> String {
+        let mut large_text = self.templates.get_large_text(file_info);
+        if large_text.is_empty() {
+            large_text = self.assets.large_text.clone();
+        }
+
+        utils::truncate_string_fmt(&mut large_text, MAX_STR_LEN);
+        large_text
+    }
+
+    fn get_activity_type(&self, media_kind: MediaKind) -> ActivityType {
+        let name = match media_kind {
+            MediaKind::Audio => self.activity_types.audio,
+            MediaKind::Video => self.activity_types.video
+        };
+
+        match name {
+            ActivityTypeName::Playing => ActivityType::Playing,
+            ActivityTypeName::Listening => ActivityType::Listening,
+            ActivityTypeName::Watching => ActivityType::Watching,
+            ActivityTypeName::Competing => ActivityType::Competing
+        }
     }
 
-    fn get_large_info(cover_art: bool, metadata: FileMetadata) -> (String, String) {
-        if !cover_art {
-            return ("logo".to_string(), "mpv".to_string())
+    // Only the kind of media is revealed for private files
+    fn get_private_details(media_kind: MediaKind) -> String {
+        match media_kind {
+            MediaKind::Audio => String::from("Listening to something"),
+            MediaKind::Video => String::from("Watching something")
         }
+    }
 
-        let cover_art_url = music_brainz::get_cover_art_url(&metadata.title, &metadata.album, &metadata.artist, &metadata.album_artist);
-        let large_image = match cover_art_url {
-            Some(url) => url,
-            None => "logo".to_string()
-        };
-        let large_text = match metadata.title.or(metadata.album) {
-            Some(text) => text,
-            None => "mpv".to_string()
-        };
+    // The cover art itself is filled in later by the worker,
+    // until then the logo is displayed
+    fn get_assets_info(&self, file_info: &FileInfo) -> AssetsInfo {
+        AssetsInfo::new(self.assets.large_image.clone(), self.get_large_text(file_info), self.get_small_image())
+    }
 
-        (large_image, large_text)
+    fn get_small_image(&self) -> Option<String> {
+        match self.paused {
+            true => self.assets.paused_image.clone(),
+            false => self.assets.playing_image.clone()
+        }
     }
 
     fn update_presence(&mut self) -> Result<(), &'static str> {
-        if !self.active {
+        if !self.connection.is_connected() {
             return Ok(())
         }
 
         logging::info!(self.logger, "Updating rich presence");
 
-        match self.discord.set_activity(self.activity_info.get_activity()) {
+        let result = match self.privacy_action {
+            Some(PrivacyAction::Hide) => self.discord.clear_activity(),
+            _ => self.discord.set_activity(self.activity_info.get_activity())
+        };
+
+        match result {
             Ok(()) => {
                 Ok(())
             }
             Err(_) => {
-                self.active = false;
+                // The socket might still be open if only sending has failed
+                let _ = self.discord.close();
+                self.transition(ConnectionEvent::ConnectionLost);
                 Err("cannot set presence")
             }
         }
     }
 
-    fn set_presence(&mut self, file_info: FileInfo) -> Result<(), &'static str> {
-        let details = DiscordClient::get_details(&file_info);
-        let state = DiscordClient::get_state(&file_info);
-        let assets_info = DiscordClient::get_assets_info(self.cover_art, file_info.metadata);
+    fn set_presence(&mut self, file_info: FileInfo, timestamps: Timestamps) -> Result<(), &'static str> {
+        // Results of the lookups for previous files are stale from now on
+        self.cover_art_request += 1;
+
+        self.privacy_action = self.privacy.get_action(&file_info);
+        match self.privacy_action {
+            Some(_) => self.set_private_presence(&file_info, timestamps),
+            None => self.set_public_presence(&file_info, timestamps)
+        }
+
+        // The presence is published without cover art if it can't be looked up
+        let metadata = file_info.metadata.clone();
+        self.file_info = Some(file_info);
+        let result = self.update_presence();
+
+        if self.cover_art && self.privacy_action.is_none() {
+            if let Err(e) = self.request_cover_art(metadata) {
+                logging::error!(self.logger, "Failed to request cover art: {e}");
+            }
+        }
+
+        result
+    }
+
+    fn set_public_presence(&mut self, file_info: &FileInfo, timestamps: Timestamps) {
+        let activity_type = self.get_activity_type(file_info.media_kind);
+        let details = self.get_details(file_info);
+        let state = self.get_state(file_info);
+        let assets_info = self.get_assets_info(file_info);
+
+        self.activity_info = ActivityInfo::new(activity_type, details, state, assets_info, timestamps);
+        self.activity_info.release_button = self.get_tagged_release_button(&file_info.metadata);
+        self.activity_info.source_button = self.get_source_button(&file_info.path);
+    }
+
+    // No buttons and no cover art, the lookup would send the metadata out as well
+    fn set_private_presence(&mut self, file_info: &FileInfo, timestamps: Timestamps) {
+        logging::info!(self.logger, "Keeping file private");
+
+        let activity_type = self.get_activity_type(file_info.media_kind);
+        let details = DiscordClient::get_private_details(file_info.media_kind);
+        let assets_info = AssetsInfo::new(self.assets.large_image.clone(), self.assets.large_text.clone(), self.get_small_image());
+
+        self.activity_info = ActivityInfo::new(activity_type, details, String::from("Private"), assets_info, timestamps);
+    }
 
-        self.activity_info = ActivityInfo::new(details, state, assets_info, Timestamps::new());
+    // Streams change songs without loading a new file,
+    // the presence is rebuilt while the playback keeps going
+    fn set_metadata(&mut self, metadata: FileMetadata) -> Result<(), &'static str> {
+        let mut file_info = match self.file_info.take() {
+            Some(file_info) => file_info,
+            None => return Ok(())
+        };
+
+        if file_info.metadata == metadata {
+            self.file_info = Some(file_info);
+            return Ok(());
+        }
+
+        logging::info!(self.logger, "File metadata changed");
+        file_info.metadata = metadata;
+
+        let timestamps = self.activity_info.timestamps.clone();
+        self.set_presence(file_info, timestamps)
+    }
+
+    // Only the text changes, cover art and buttons stay the same
+    fn set_chapter(&mut self, chapter: Option<String>) -> Result<(), &'static str> {
+        let mut file_info = match self.file_info.take() {
+            Some(file_info) => file_info,
+            None => return Ok(())
+        };
+
+        file_info.chapter = chapter;
+        if self.privacy_action.is_some() {
+            self.file_info = Some(file_info);
+            return Ok(());
+        }
+
+        self.activity_info.details = self.get_details(&file_info);
+        self.activity_info.state = self.get_state(&file_info);
+        self.activity_info.assets.large_text = self.get_large_text(&file_info);
+
+        self.file_info = Some(file_info);
         self.update_presence()
     }
 
-    fn set_timestamps(&mut self, remaining_time: i64) -> Result<(), &'static str> {
+    // Tracks are still being added or selected after the file has loaded
+    fn set_media_kind(&mut self, media_kind: MediaKind) -> Result<(), &'static str> {
+        let file_info = match self.file_info.as_mut() {
+            Some(file_info) => file_info,
+            None => return Ok(())
+        };
+
+        if file_info.media_kind == media_kind {
+            return Ok(());
+        }
+
+        logging::info!(self.logger, "Media kind changed");
+        file_info.media_kind = media_kind;
+        self.activity_info.activity_type = self.get_activity_type(media_kind);
+        if self.privacy_action.is_some() {
+            self.activity_info.details = DiscordClient::get_private_details(media_kind);
+        }
+
+        self.update_presence()
+    }
+
+    // Tagged releases link to MusicBrainz right away,
+    // others only once the cover art lookup has matched one
+    fn get_tagged_release_button(&self, metadata: &FileMetadata) -> Option<ButtonInfo> {
+        let template = self.buttons.release.as_ref()?;
+        let release_id = metadata.release_id.as_ref()?;
+
+        ButtonInfo::from_template(template, &format!("{}/{release_id}", cover_art_provider::RELEASE_PAGE_URL))
+    }
+
+    fn get_source_button(&self, path: &str) -> Option<ButtonInfo> {
+        let template = self.buttons.source.as_ref()?;
+        ButtonInfo::from_template(template, path)
+    }
+
+    fn request_cover_art(&mut self, metadata: FileMetadata) -> Result<(), &'static str> {
+        logging::info!(self.logger, "Requesting cover art");
+
+        let request = CoverArtRequest {
+            id: self.cover_art_request,
+            metadata
+        };
+
+        self.cover_art_worker.request(request)
+    }
+
+    pub fn update_cover_art(&mut self) -> Result<(), &'static str> {
+        let result = match self.cover_art_worker.next_result() {
+            Some(result) => result,
+            None => return Ok(())
+        };
+
+        self.set_cover_art(result)
+    }
+
+    fn set_cover_art(&mut self, result: CoverArtResult) -> Result<(), &'static str> {
+        if result.id != self.cover_art_request {
+            logging::info!(self.logger, "Discarding stale cover art");
+            return Ok(());
+        }
+
+        match result.cover_art {
+            Some(cover_art) => {
+                let provider = result.provider.unwrap_or("cache");
+                logging::info!(self.logger, "Cover art found ({provider}): {}", cover_art.url);
+                self.activity_info.assets.large_image = cover_art.url;
+
+                if self.activity_info.release_button.is_none() {
+                    let template = self.buttons.release.as_ref();
+                    let release_url = cover_art.release_url.as_ref();
+                    self.activity_info.release_button = template.zip(release_url)
+                                                                .and_then(|(template, url)| ButtonInfo::from_template(template, url));
+                }
+
+                self.update_presence()
+            }
+            None => {
+                logging::info!(self.logger, "Cover art not found");
+                Ok(())
+            }
+        }
+    }
+
+    fn set_timestamps(&mut self, time: PlaybackTime) -> Result<(), &'static str> {
         let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
         let current_time = match current_time {
             Ok(time) => time.as_secs() as i64,
             Err(_) => return Err("cannot get current system time")
         };
 
-        let predicted_time = current_time + remaining_time;
+        // Without a known duration only the elapsed time can be shown
+        self.activity_info.timestamps = match (self.timestamps, time.remaining) {
+            (TimestampsMode::Remaining, Some(remaining_time)) => Timestamps::new().end(current_time + remaining_time),
+            _ => Timestamps::new().start(current_time - time.position)
+        };
 
-        self.activity_info.timestamps = Timestamps::new().end(predicted_time);
         self.update_presence()
     }
 
+    fn set_playing(&mut self, time: PlaybackTime) -> Result<(), &'static str> {
+        self.paused = false;
+        self.activity_info.assets.small_image = self.get_small_image();
+        self.set_timestamps(time)
+    }
+
+    fn set_paused(&mut self) -> Result<(), &'static str> {
+        self.paused = true;
+        self.activity_info.assets.small_image = self.get_small_image();
+        self.clear_timestamps()
+    }
+
+    // Seeking or buffering while paused doesn't resume playback
+    fn restore_timestamps(&mut self, time: PlaybackTime) -> Result<(), &'static str> {
+        match self.paused {
+            false => self.set_timestamps(time),
+            true => Ok(())
+        }
+    }
+
     fn clear_timestamps(&mut self) -> Result<(), &'static str> {
         self.activity_info.timestamps = Timestamps::new();
         self.update_presence()
     }
 
     fn open(&mut self) -> Result<(), &'static str> {
-        if self.active {
+        if self.connection.is_enabled() {
             return Ok(());
         }
 
         logging::info!(self.logger, "Opening discord client");
-        match self.discord.connect() {
-            Ok(()) => {
-                self.active = true;
-                self.request_osd_message("Discord RPC started");
-                self.update_presence()
-            }
-            Err(_) => Err("cannot connect to Discord")
+        let connected = self.discord.connect().is_ok();
+        if !connected {
+            logging::info!(self.logger, "Cannot connect to Discord, retrying later");
         }
+
+        self.transition(ConnectionEvent::Enable { connected });
+        self.update_presence()
     }
 
     fn close(&mut self) -> Result<(), &'static str> {
-        if !self.active {
+        if !self.connection.is_enabled() {
             return Ok(());
         }
 
         logging::info!(self.logger, "Closing discord client");
-        match self.discord.close() {
-            Ok(()) => {
-                self.active = false;
-                self.request_osd_message("Discord RPC stopped");
-                Ok(())
-            }
+
+        // Nothing to disconnect from while waiting for Discord
+        let closed = match self.connection.is_connected() {
+            true => self.discord.close(),
+            false => Ok(())
+        };
+
+        self.transition(ConnectionEvent::Disable);
+        match closed {
+            Ok(()) => Ok(()),
             Err(_) => Err("cannot disconnect from Discord")
         }
     }
 
+    // Called periodically from the event loop,
+    // retries the connection once the backoff delay has passed
+    pub fn update_connection(&mut self) -> Result<(), &'static str> {
+        if !self.connection.is_retry_due() {
+            return Ok(());
+        }
+
+        let connected = self.discord.connect().is_ok();
+        self.transition(ConnectionEvent::Retry { connected });
+
+        match connected {
+            // Replay the current activity
+            true => self.update_presence(),
+            false => Ok(())
+        }
+    }
+
+    fn transition(&mut self, event: ConnectionEvent) {
+        if let Some(message) = self.connection.transition(event) {
+            logging::info!(self.logger, "{message}");
+            self.request_osd_message(message);
+        }
+    }
+
     fn toggle_activity(&mut self) -> Result<(), &'static str> {
-        match self.active {
+        match self.connection.is_enabled() {
             false => self.open(),
             true => self.close()
         }
     }
 
+    fn handle_command(&mut self, command: Command) -> Result<(), &'static str> {
+        match command {
+            Command::Enable => self.enable(),
+            Command::Disable => self.disable(),
+            Command::Toggle => self.toggle_activity(),
+            Command::Refresh => {
+                self.request_osd_message("Discord RPC presence refreshed");
+                self.refresh_presence()
+            }
+            Command::SetDetails(details) => {
+                self.details_override = Some(details);
+                self.request_osd_message("Discord RPC details set");
+                self.refresh_presence()
+            }
+            Command::SetState(state) => {
+                self.state_override = Some(state);
+                self.request_osd_message("Discord RPC state set");
+                self.refresh_presence()
+            }
+            Command::ClearOverride => {
+                self.details_override = None;
+                self.state_override = None;
+                self.request_osd_message("Discord RPC details and state restored");
+                self.refresh_presence()
+            }
+            Command::Privacy(enabled) => self.set_privacy(enabled),
+            Command::TogglePrivacy => self.set_privacy(!self.privacy.is_enabled()),
+
+            // The plugin owns the config, reloading is done there
+            Command::ReloadConfig => Ok(())
+        }
+    }
+
+    // Commands report the current state even if nothing has changed,
+    // so scripts always get a reply
+    fn enable(&mut self) -> Result<(), &'static str> {
+        match self.connection.is_enabled() {
+            true => {
+                self.request_osd_message("Discord RPC already enabled");
+                Ok(())
+            }
+            false => self.open()
+        }
+    }
+
+    fn disable(&mut self) -> Result<(), &'static str> {
+        match self.connection.is_enabled() {
+            true => self.close(),
+            false => {
+                self.request_osd_message("Discord RPC already disabled");
+                Ok(())
+            }
+        }
+    }
+
+    fn set_privacy(&mut self, enabled: bool) -> Result<(), &'static str> {
+        self.privacy.set_enabled(enabled);
+
+        let message = match enabled {
+            true => "Discord RPC privacy mode enabled",
+            false => "Discord RPC privacy mode disabled"
+        };
+        logging::info!(self.logger, "{message}");
+        self.request_osd_message(message);
+
+        self.refresh_presence()
+    }
+
+    // The current file is published again, keeping its timestamps
+    fn refresh_presence(&mut self) -> Result<(), &'static str> {
+        match self.file_info.take() {
+            Some(file_info) => {
+                let timestamps = self.activity_info.timestamps.clone();
+                self.set_presence(file_info, timestamps)
+            }
+            None => Ok(())
+        }
+    }
+
     fn request_osd_message(&mut self, message: &'static str) {
         self.mpv_requests.push_front(MpvRequest::OSDMessage(message));
     }
@@ -262,12 +713,21 @@ impl DiscordClient {
 impl MpvEventHandler for DiscordClient {
     fn handle_event(&mut self, event: MpvEvent) -> Result<(), &'static str> {
         match event {
-            MpvEvent::FileLoaded(file_info) => self.set_presence(file_info),
-            MpvEvent::Seek(remaining_time) => self.set_timestamps(remaining_time),
-            MpvEvent::Play(remaining_time) => self.set_timestamps(remaining_time),
-            MpvEvent::Pause => self.clear_timestamps(),
+            MpvEvent::FileLoaded(file_info) => self.set_presence(*file_info, Timestamps::new()),
+            MpvEvent::MetadataChange(metadata) => self.set_metadata(*metadata),
+            MpvEvent::Seek(time) => self.restore_timestamps(time),
+            MpvEvent::SpeedChange(time) => self.restore_timestamps(time),
+            MpvEvent::Play(time) => self.set_playing(time),
+            MpvEvent::Chapter(chapter) => self.set_chapter(chapter),
+            MpvEvent::MediaKindChange(media_kind) => self.set_media_kind(media_kind),
+            MpvEvent::Pause => self.set_paused(),
             MpvEvent::Buffering => self.clear_timestamps(),
-            MpvEvent::Toggle => self.toggle_activity(),
+            MpvEvent::BufferingEnd(time) => self.restore_timestamps(time),
+            MpvEvent::Command(command) => self.handle_command(command),
+            MpvEvent::InvalidCommand(message) => {
+                self.request_osd_message(message);
+                Ok(())
+            }
             MpvEvent::Exit => self.close(),
         }
     }
@@ -278,3 +738,228 @@ impl MpvRequester for DiscordClient {
         self.mpv_requests.pop_back()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use crate::config::AssetsConfig;
+    use crate::logging::LogLevel;
+    use crate::test_utils::{self, TempDir};
+    use super::*;
+    use super::fake_discord::FakeDiscord;
+
+    // Every test has a server of its own, the client is given its socket
+    fn fake_discord() -> (TempDir, FakeDiscord) {
+        let dir = TempDir::new("discord");
+        let discord = FakeDiscord::bind(&get_socket_path(&dir)).unwrap();
+
+        (dir, discord)
+    }
+
+    fn get_socket_path(dir: &TempDir) -> PathBuf {
+        dir.path().join("discord-ipc-0")
+    }
+
+    fn config() -> Config {
+        Config {
+            active: true,
+            cover_art: false,
+            cover_art_cache: false,
+            ..Config::default()
+        }
+    }
+
+    // The empty activity sent when connecting isn't part of the checked presence
+    fn connect(dir: &TempDir, discord: &FakeDiscord, config: &Config) -> DiscordClient {
+        let logger = Rc::new(Logger::new(LogLevel::None));
+        let client = DiscordClient::with_socket("1234", Some(get_socket_path(dir)), config, logger).unwrap();
+        assert!(client.connection.is_connected());
+        discord.wait_for_activities(1);
+        assert_eq!(discord.get_client_ids(), vec!["1234"]);
+        discord.clear();
+        client
+    }
+
+    fn file_loaded() -> MpvEvent {
+        MpvEvent::FileLoaded(Box::new(test_utils::song_file_info()))
+    }
+
+    fn current_time() -> i64 {
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    fn assert_around(value: &Value, expected: i64) {
+        let value = value.as_i64().unwrap_or_else(|| panic!("{value} isn't a timestamp"));
+        assert!((value - expected).abs() <= 2, "{value} isn't close to {expected}");
+    }
+
+    #[test]
+    fn publishes_playback_of_file() {
+        let (dir, discord) = fake_discord();
+        let mut client = connect(&dir, &discord, &config());
+
+        client.handle_event(file_loaded()).unwrap();
+        client.handle_event(MpvEvent::Play(PlaybackTime { remaining: Some(180), position: 20 })).unwrap();
+        client.handle_event(MpvEvent::Pause).unwrap();
+
+        let activities = discord.wait_for_activities(3);
+        assert_eq!(activities.len(), 3);
+
+        let loaded = json!({
+            "type": 2,
+            "details": "Title",
+            "state": "by Artist on Album",
+            "assets": { "large_image": "logo", "large_text": "Title" },
+            "timestamps": {}
+        });
+        assert_eq!(activities[0], loaded);
+
+        let playing = &activities[1];
+        assert_eq!(playing["details"], "Title");
+        assert_around(&playing["timestamps"]["end"], current_time() + 180);
+        assert!(playing["timestamps"].get("start").is_none());
+
+        assert_eq!(activities[2], loaded);
+    }
+
+    #[test]
+    fn shows_elapsed_time_without_duration() {
+        let (dir, discord) = fake_discord();
+        let mut client = connect(&dir, &discord, &config());
+
+        client.handle_event(file_loaded()).unwrap();
+        client.handle_event(MpvEvent::Play(PlaybackTime { remaining: None, position: 30 })).unwrap();
+
+        let activities = discord.wait_for_activities(2);
+        assert_around(&activities[1]["timestamps"]["start"], current_time() - 30);
+        assert!(activities[1]["timestamps"].get("end").is_none());
+    }
+
+    #[test]
+    fn publishes_configured_assets() {
+        let (dir, discord) = fake_discord();
+        let config = Config {
+            assets: AssetsConfig {
+                large_image: "brand".to_string(),
+                large_text: "Player".to_string(),
+                playing_image: Some("play".to_string()),
+                paused_image: Some("pause".to_string())
+            },
+            ..config()
+        };
+        let mut client = connect(&dir, &discord, &config);
+
+        client.handle_event(file_loaded()).unwrap();
+        client.handle_event(MpvEvent::Pause).unwrap();
+        client.handle_event(MpvEvent::Play(PlaybackTime { remaining: Some(100), position: 0 })).unwrap();
+
+        let activities = discord.wait_for_activities(3);
+        let small_images: Vec<&Value> = activities.iter()
+                                                  .map(|activity| &activity["assets"]["small_image"])
+                                                  .collect();
+        assert_eq!(small_images, vec!["play", "pause", "play"]);
+        assert_eq!(activities[0]["assets"]["large_image"], "brand");
+    }
+
+    #[test]
+    fn hidden_files_clear_the_activity() {
+        let (dir, discord) = fake_discord();
+        let mut config = config();
+        config.privacy.action = PrivacyAction::Hide;
+        let mut client = connect(&dir, &discord, &config);
+
+        client.handle_event(MpvEvent::Command(Command::Privacy(true))).unwrap();
+        client.handle_event(file_loaded()).unwrap();
+
+        let activities = discord.wait_for_activities(1);
+        assert_eq!(activities, vec![Value::Null]);
+    }
+
+    #[test]
+    fn private_files_are_published_generically() {
+        let (dir, discord) = fake_discord();
+        let mut client = connect(&dir, &discord, &config());
+
+        client.handle_event(MpvEvent::Command(Command::Privacy(true))).unwrap();
+        client.handle_event(file_loaded()).unwrap();
+
+        let activities = discord.wait_for_activities(1);
+        assert_eq!(activities[0]["details"], "Listening to something");
+        assert_eq!(activities[0]["state"], "Private");
+        assert_eq!(activities[0]["assets"], json!({ "large_image": "logo", "large_text": "mpv" }));
+    }
+
+    #[test]
+    fn config_change_reconnects_and_applies_settings() {
+        let (dir, discord) = fake_discord();
+        let previous = config();
+        let mut client = connect(&dir, &discord, &previous);
+        client.handle_event(file_loaded()).unwrap();
+        discord.wait_for_activities(1);
+        discord.clear();
+
+        let mut config = config();
+        config.application_id = "5678".to_string();
+        config.assets.large_image = "brand".to_string();
+        client.set_config(&config, &previous).unwrap();
+
+        // The current activity is sent again when reconnecting, then refreshed with the new settings
+        let activities = discord.wait_for_activities(2);
+        assert_eq!(discord.get_client_ids(), vec!["5678"]);
+        assert_eq!(activities.len(), 2);
+        assert_eq!(activities[1]["details"], "Title");
+        assert_eq!(activities[1]["assets"]["large_image"], "brand");
+    }
+
+    #[test]
+    fn config_is_applied_without_discord() {
+        let (dir, discord) = fake_discord();
+        let previous = config();
+        let mut client = connect(&dir, &discord, &previous);
+        client.handle_event(file_loaded()).unwrap();
+        discord.wait_for_activities(1);
+        discord.disconnect();
+
+        let mut config = config();
+        config.assets.large_image = "brand".to_string();
+        assert!(client.set_config(&config, &previous).is_ok());
+        assert!(!client.connection.is_connected());
+        assert_eq!(client.activity_info.assets.large_image, "brand");
+    }
+
+    #[test]
+    fn presence_is_published_when_cover_art_fails() {
+        let (dir, discord) = fake_discord();
+        let config = Config {
+            cover_art: true,
+            ..config()
+        };
+        let mut client = connect(&dir, &discord, &config);
+        client.cover_art_worker = CoverArtWorker::stopped();
+
+        client.handle_event(file_loaded()).unwrap();
+        client.handle_event(MpvEvent::Chapter(Some("Chapter".to_string()))).unwrap();
+
+        let activities = discord.wait_for_activities(2);
+        assert_eq!(activities.len(), 2);
+        assert_eq!(activities[0]["details"], "Title");
+        assert_eq!(activities[0]["assets"]["large_image"], "logo");
+        assert!(client.file_info.as_ref().is_some_and(|file_info| file_info.chapter.as_deref() == Some("Chapter")));
+    }
+
+    #[test]
+    fn nothing_is_sent_after_disabling() {
+        let (dir, discord) = fake_discord();
+        let mut client = connect(&dir, &discord, &config());
+
+        client.handle_event(MpvEvent::Command(Command::Disable)).unwrap();
+        client.handle_event(file_loaded()).unwrap();
+        assert!(matches!(client.next_request(), Some(MpvRequest::OSDMessage(_))));
+
+        client.handle_event(MpvEvent::Command(Command::Enable)).unwrap();
+        let activities = discord.wait_for_activities(1);
+        assert_eq!(activities[0]["details"], "Title");
+        assert_eq!(activities.len(), 1);
+        assert_eq!(discord.get_client_ids(), vec!["1234"]);
+    }
+}
@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+const INITIAL_DELAY: Duration = Duration::from_secs(2);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+// Delay between reconnection attempts, doubled after every failure
+pub struct Backoff {
+    delay: Duration,
+    next_attempt: Instant
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self {
+            delay: INITIAL_DELAY,
+            next_attempt: Instant::now() + INITIAL_DELAY
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_attempt
+    }
+
+    #[cfg(test)]
+    pub fn get_delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn fail(&mut self) {
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+        self.next_attempt = Instant::now() + self.delay;
+    }
+}
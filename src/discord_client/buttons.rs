@@ -0,0 +1,50 @@
+use reqwest::Url;
+use crate::utils;
+
+// Discord rejects buttons with longer labels or URLs
+const MAX_LABEL_LEN: usize = 32;
+const MAX_URL_LEN: usize = 512;
+
+pub struct ButtonInfo {
+    pub label: String,
+    pub url: String
+}
+
+impl ButtonInfo {
+    // Only web links are published, anything else could be a local path.
+    // Credentials, query and fragment are stripped as well
+    pub fn from_template(template: &str, url: &str) -> Option<Self> {
+        let mut url = Url::parse(url).ok()?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+
+        url.set_username("").ok()?;
+        url.set_password(None).ok()?;
+        url.set_fragment(None);
+
+        let host = url.host_str()?.trim_start_matches("www.").to_string();
+        let url = ButtonInfo::strip_query(url);
+        if url.len() > MAX_URL_LEN {
+            return None;
+        }
+
+        let mut label = template.replace("{host}", &host);
+        utils::truncate_string_fmt(&mut label, MAX_LABEL_LEN);
+
+        Some(Self {
+            label,
+            url
+        })
+    }
+
+    // YouTube keeps the video ID in the query, so it stays for known hosts
+    fn strip_query(mut url: Url) -> String {
+        let keep_query = matches!(url.host_str(), Some("youtube.com" | "www.youtube.com" | "m.youtube.com" | "music.youtube.com"));
+        if !keep_query {
+            url.set_query(None);
+        }
+
+        url.to_string()
+    }
+}
@@ -0,0 +1,205 @@
+use std::mem;
+use super::backoff::Backoff;
+
+// Whether the user wants RPC enabled and whether Discord is actually connected.
+// A disabled client is never connected, an enabled one is either
+// connected or waiting to retry the connection
+pub enum ConnectionState {
+    Disabled,
+    Waiting(Backoff),
+    Connected
+}
+
+// Inputs of the state machine, outcomes of connection attempts
+// are passed along with the event that triggered them
+pub enum ConnectionEvent {
+    Enable { connected: bool },
+    Disable,
+    ConnectionLost,
+    Retry { connected: bool }
+}
+
+impl ConnectionState {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, ConnectionState::Disabled)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected)
+    }
+
+    pub fn is_retry_due(&self) -> bool {
+        match self {
+            ConnectionState::Waiting(backoff) => backoff.is_due(),
+            _ => false
+        }
+    }
+
+    // Returns the message describing the change, if the state has changed
+    pub fn transition(&mut self, event: ConnectionEvent) -> Option<&'static str> {
+        let state = mem::replace(self, ConnectionState::Disabled);
+        let (state, message) = ConnectionState::next(state, event);
+
+        *self = state;
+        message
+    }
+
+    fn next(state: ConnectionState, event: ConnectionEvent) -> (ConnectionState, Option<&'static str>) {
+        match (state, event) {
+            (ConnectionState::Disabled, ConnectionEvent::Enable { connected: true }) => {
+                (ConnectionState::Connected, Some("Discord RPC enabled"))
+            }
+            (ConnectionState::Disabled, ConnectionEvent::Enable { connected: false }) => {
+                (ConnectionState::Waiting(Backoff::new()), Some("Discord RPC enabled (waiting for Discord)"))
+            }
+            (ConnectionState::Disabled, ConnectionEvent::Disable) => {
+                (ConnectionState::Disabled, None)
+            }
+            (_, ConnectionEvent::Disable) => {
+                (ConnectionState::Disabled, Some("Discord RPC disabled"))
+            }
+            (ConnectionState::Connected, ConnectionEvent::ConnectionLost) => {
+                (ConnectionState::Waiting(Backoff::new()), Some("Discord RPC enabled (connection lost, waiting for Discord)"))
+            }
+            (ConnectionState::Waiting(_), ConnectionEvent::Retry { connected: true }) => {
+                (ConnectionState::Connected, Some("Discord RPC connected"))
+            }
+            (ConnectionState::Waiting(mut backoff), ConnectionEvent::Retry { connected: false }) => {
+                backoff.fail();
+                (ConnectionState::Waiting(backoff), None)
+            }
+
+            // Enabling twice, losing a connection that wasn't there
+            // or retrying when not waiting changes nothing
+            (state, _) => (state, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Kind {
+        Disabled,
+        Waiting,
+        Connected
+    }
+
+    fn kind(state: &ConnectionState) -> Kind {
+        match state {
+            ConnectionState::Disabled => Kind::Disabled,
+            ConnectionState::Waiting(_) => Kind::Waiting,
+            ConnectionState::Connected => Kind::Connected
+        }
+    }
+
+    fn state(kind: &Kind) -> ConnectionState {
+        match kind {
+            Kind::Disabled => ConnectionState::Disabled,
+            Kind::Waiting => ConnectionState::Waiting(Backoff::new()),
+            Kind::Connected => ConnectionState::Connected
+        }
+    }
+
+    fn event(name: &str) -> ConnectionEvent {
+        match name {
+            "enable connected" => ConnectionEvent::Enable { connected: true },
+            "enable waiting" => ConnectionEvent::Enable { connected: false },
+            "disable" => ConnectionEvent::Disable,
+            "connection lost" => ConnectionEvent::ConnectionLost,
+            "retry connected" => ConnectionEvent::Retry { connected: true },
+            "retry failed" => ConnectionEvent::Retry { connected: false },
+            _ => unreachable!()
+        }
+    }
+
+    fn assert_transition(from: Kind, event_name: &str, to: Kind, message: Option<&str>) {
+        let mut connection = state(&from);
+        let actual_message = connection.transition(event(event_name));
+
+        assert_eq!(kind(&connection), to, "state after {event_name} from {from:?}");
+        assert_eq!(actual_message, message, "message after {event_name} from {from:?}");
+    }
+
+    #[test]
+    fn transitions_from_disabled() {
+        assert_transition(Kind::Disabled, "enable connected", Kind::Connected, Some("Discord RPC enabled"));
+        assert_transition(Kind::Disabled, "enable waiting", Kind::Waiting, Some("Discord RPC enabled (waiting for Discord)"));
+        assert_transition(Kind::Disabled, "disable", Kind::Disabled, None);
+        assert_transition(Kind::Disabled, "connection lost", Kind::Disabled, None);
+        assert_transition(Kind::Disabled, "retry connected", Kind::Disabled, None);
+        assert_transition(Kind::Disabled, "retry failed", Kind::Disabled, None);
+    }
+
+    #[test]
+    fn transitions_from_waiting() {
+        assert_transition(Kind::Waiting, "enable connected", Kind::Waiting, None);
+        assert_transition(Kind::Waiting, "enable waiting", Kind::Waiting, None);
+        assert_transition(Kind::Waiting, "disable", Kind::Disabled, Some("Discord RPC disabled"));
+        assert_transition(Kind::Waiting, "connection lost", Kind::Waiting, None);
+        assert_transition(Kind::Waiting, "retry connected", Kind::Connected, Some("Discord RPC connected"));
+        assert_transition(Kind::Waiting, "retry failed", Kind::Waiting, None);
+    }
+
+    #[test]
+    fn transitions_from_connected() {
+        assert_transition(Kind::Connected, "enable connected", Kind::Connected, None);
+        assert_transition(Kind::Connected, "enable waiting", Kind::Connected, None);
+        assert_transition(Kind::Connected, "disable", Kind::Disabled, Some("Discord RPC disabled"));
+        assert_transition(
+            Kind::Connected,
+            "connection lost",
+            Kind::Waiting,
+            Some("Discord RPC enabled (connection lost, waiting for Discord)")
+        );
+        assert_transition(Kind::Connected, "retry connected", Kind::Connected, None);
+        assert_transition(Kind::Connected, "retry failed", Kind::Connected, None);
+    }
+
+    fn get_delay(state: &ConnectionState) -> Duration {
+        match state {
+            ConnectionState::Waiting(backoff) => backoff.get_delay(),
+            _ => panic!("not waiting")
+        }
+    }
+
+    #[test]
+    fn failed_retries_grow_backoff_up_to_limit() {
+        let mut connection = ConnectionState::Disabled;
+        connection.transition(ConnectionEvent::Enable { connected: false });
+        assert_eq!(get_delay(&connection), Duration::from_secs(2));
+        assert!(!connection.is_retry_due());
+
+        let mut delays = Vec::new();
+        for _ in 0..7 {
+            connection.transition(ConnectionEvent::Retry { connected: false });
+            delays.push(get_delay(&connection).as_secs());
+        }
+
+        assert_eq!(delays, vec![4, 8, 16, 32, 60, 60, 60]);
+        assert!(connection.is_enabled());
+        assert!(!connection.is_connected());
+    }
+
+    #[test]
+    fn lost_connection_starts_over_with_initial_backoff() {
+        let mut connection = ConnectionState::Waiting(Backoff::new());
+        connection.transition(ConnectionEvent::Retry { connected: false });
+        connection.transition(ConnectionEvent::Retry { connected: true });
+        assert!(connection.is_connected());
+
+        connection.transition(ConnectionEvent::ConnectionLost);
+        assert_eq!(get_delay(&connection), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn only_waiting_state_retries() {
+        assert!(!ConnectionState::Disabled.is_retry_due());
+        assert!(!ConnectionState::Connected.is_retry_due());
+        assert!(!ConnectionState::Disabled.is_enabled());
+        assert!(ConnectionState::Connected.is_enabled());
+    }
+}
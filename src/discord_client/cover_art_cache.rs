@@ -0,0 +1,256 @@
+use std::fs;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+use crate::mpv_event_queue::events::FileMetadata;
+use super::cover_art_provider::CoverArt;
+
+const MAX_ENTRIES: usize = 2048;
+
+// Lookups that found nothing are retried after a day,
+// found URLs are kept for a month
+const NEGATIVE_TTL: u64 = 60 * 60 * 24;
+const POSITIVE_TTL: u64 = 60 * 60 * 24 * 30;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    cover_art: Option<CoverArt>,
+    timestamp: u64
+}
+
+impl CacheEntry {
+    fn is_expired(&self, now: u64) -> bool {
+        let ttl = match self.cover_art {
+            Some(_) => POSITIVE_TTL,
+            None => NEGATIVE_TTL
+        };
+
+        now.saturating_sub(self.timestamp) > ttl
+    }
+}
+
+pub struct CoverArtCache {
+    path: String,
+    entries: HashMap<String, CacheEntry>
+}
+
+impl CoverArtCache {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            entries: HashMap::new()
+        }
+    }
+
+    pub fn load(&mut self) {
+        let json = match fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(_) => return
+        };
+
+        // A broken cache is not worth reporting, it gets overwritten on the next save
+        if let Ok(entries) = serde_json::from_str(&json) {
+            self.entries = entries;
+        }
+
+        let now = CoverArtCache::current_time();
+        self.entries.retain(|_, entry| !entry.is_expired(now));
+    }
+
+    pub fn save(&self) {
+        if let Ok(json) = serde_json::to_string(&self.entries) {
+            let _ = fs::write(&self.path, json);
+        }
+    }
+
+    // Outer None means the metadata isn't cached,
+    // inner None means no cover art was found last time
+    pub fn get(&self, metadata: &FileMetadata) -> Option<Option<CoverArt>> {
+        let entry = self.entries.get(&CoverArtCache::get_key(metadata))?;
+
+        if entry.is_expired(CoverArtCache::current_time()) {
+            return None;
+        }
+
+        Some(entry.cover_art.clone())
+    }
+
+    pub fn insert(&mut self, metadata: &FileMetadata, cover_art: Option<CoverArt>) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.evict_oldest();
+        }
+
+        let entry = CacheEntry {
+            cover_art,
+            timestamp: CoverArtCache::current_time()
+        };
+
+        self.entries.insert(CoverArtCache::get_key(metadata), entry);
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self.entries.iter()
+                                 .min_by_key(|(_, entry)| entry.timestamp)
+                                 .map(|(key, _)| key.clone());
+
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+
+    fn get_key(metadata: &FileMetadata) -> String {
+        let fields = [
+            &metadata.artist, &metadata.album_artist, &metadata.album, &metadata.title,
+            &metadata.release_id, &metadata.release_group_id, &metadata.recording_id
+        ];
+        let fields: Vec<String> = fields.iter()
+                                        .map(|field| CoverArtCache::normalize(field))
+                                        .collect();
+
+        fields.join("\u{1f}")
+    }
+
+    fn normalize(field: &Option<String>) -> String {
+        match field {
+            Some(field) => field.split_whitespace()
+                                .collect::<Vec<&str>>()
+                                .join(" ")
+                                .to_lowercase(),
+            None => String::new()
+        }
+    }
+
+    fn current_time() -> u64 {
+        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(time) => time.as_secs(),
+            Err(_) => 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::TempDir;
+    use super::*;
+
+    fn metadata(title: &str) -> FileMetadata {
+        FileMetadata {
+            artist: Some("Artist".to_string()),
+            title: Some(title.to_string()),
+            ..FileMetadata::default()
+        }
+    }
+
+    fn cover_art(url: &str) -> CoverArt {
+        CoverArt {
+            url: url.to_string(),
+            release_url: None
+        }
+    }
+
+    // Entry looked up the given number of seconds ago
+    fn insert_aged(cache: &mut CoverArtCache, title: &str, cover_art: Option<CoverArt>, age: u64) {
+        let entry = CacheEntry {
+            cover_art,
+            timestamp: CoverArtCache::current_time() - age
+        };
+
+        cache.entries.insert(CoverArtCache::get_key(&metadata(title)), entry);
+    }
+
+    #[test]
+    fn entries_are_found_by_normalized_metadata() {
+        let mut cache = CoverArtCache::new(String::new());
+        cache.insert(&metadata("Title"), Some(cover_art("found")));
+        cache.insert(&metadata("Missing"), None);
+
+        let found = cache.get(&metadata("  TITLE "));
+        assert_eq!(found.flatten().map(|cover_art| cover_art.url).as_deref(), Some("found"));
+        assert!(matches!(cache.get(&metadata("Missing")), Some(None)));
+        assert!(cache.get(&metadata("Other")).is_none());
+    }
+
+    #[test]
+    fn entries_expire_after_their_ttl() {
+        let mut cache = CoverArtCache::new(String::new());
+        insert_aged(&mut cache, "Recent miss", None, NEGATIVE_TTL - 60);
+        insert_aged(&mut cache, "Old miss", None, NEGATIVE_TTL + 60);
+        insert_aged(&mut cache, "Recent", Some(cover_art("recent")), POSITIVE_TTL - 60);
+        insert_aged(&mut cache, "Old", Some(cover_art("old")), POSITIVE_TTL + 60);
+
+        assert!(matches!(cache.get(&metadata("Recent miss")), Some(None)));
+        assert!(cache.get(&metadata("Old miss")).is_none());
+        assert!(cache.get(&metadata("Recent")).flatten().is_some());
+        assert!(cache.get(&metadata("Old")).is_none());
+    }
+
+    #[test]
+    fn found_cover_art_outlives_misses() {
+        let mut cache = CoverArtCache::new(String::new());
+        insert_aged(&mut cache, "Miss", None, NEGATIVE_TTL + 60);
+        insert_aged(&mut cache, "Found", Some(cover_art("found")), NEGATIVE_TTL + 60);
+
+        assert!(cache.get(&metadata("Miss")).is_none());
+        assert!(cache.get(&metadata("Found")).flatten().is_some());
+    }
+
+    #[test]
+    fn oldest_entry_is_evicted_when_full() {
+        let mut cache = CoverArtCache::new(String::new());
+        for i in 0..MAX_ENTRIES {
+            insert_aged(&mut cache, &format!("Title {i}"), None, 60 + i as u64);
+        }
+
+        cache.insert(&metadata("Newest"), None);
+
+        assert_eq!(cache.entries.len(), MAX_ENTRIES);
+        assert!(cache.get(&metadata("Newest")).is_some());
+        assert!(cache.get(&metadata(&format!("Title {}", MAX_ENTRIES - 1))).is_none());
+        assert!(cache.get(&metadata(&format!("Title {}", MAX_ENTRIES - 2))).is_some());
+        assert!(cache.get(&metadata("Title 0")).is_some());
+    }
+
+    #[test]
+    fn saved_entries_are_loaded() {
+        let dir = TempDir::new("cover-art-cache-saved");
+        let mut cache = CoverArtCache::new(dir.file("cache.json"));
+        cache.insert(&metadata("Title"), Some(cover_art("found")));
+        insert_aged(&mut cache, "Old", Some(cover_art("old")), POSITIVE_TTL + 60);
+        cache.save();
+
+        let mut loaded = CoverArtCache::new(dir.file("cache.json"));
+        loaded.load();
+
+        assert!(loaded.get(&metadata("Title")).flatten().is_some());
+
+        // Expired entries are dropped on load
+        assert_eq!(loaded.entries.len(), 1);
+    }
+
+    #[test]
+    fn corrupt_cache_is_ignored_and_overwritten() {
+        let dir = TempDir::new("cover-art-cache-corrupt");
+        let path = dir.file("cache.json");
+        fs::write(&path, "{\"broken\": ").unwrap();
+
+        let mut cache = CoverArtCache::new(path.clone());
+        cache.load();
+        assert!(cache.entries.is_empty());
+
+        cache.insert(&metadata("Title"), None);
+        cache.save();
+
+        let mut loaded = CoverArtCache::new(path);
+        loaded.load();
+        assert!(matches!(loaded.get(&metadata("Title")), Some(None)));
+    }
+
+    #[test]
+    fn missing_cache_file_is_ignored() {
+        let dir = TempDir::new("cover-art-cache-missing");
+        let mut cache = CoverArtCache::new(dir.file("cache.json"));
+        cache.load();
+
+        assert!(cache.entries.is_empty());
+    }
+}
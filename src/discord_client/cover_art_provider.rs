@@ -0,0 +1,42 @@
+use serde::{Serialize, Deserialize};
+use crate::config::CoverArtProviderName;
+use crate::mpv_event_queue::events::FileMetadata;
+
+mod music_brainz;
+mod itunes;
+
+#[cfg(test)]
+mod mock_server;
+
+use music_brainz::MusicBrainzProvider;
+pub use music_brainz::RELEASE_PAGE_URL;
+use itunes::ITunesProvider;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CoverArt {
+    pub url: String,
+
+    // Page of the matched release, if the provider has one
+    pub release_url: Option<String>
+}
+
+pub trait CoverArtProvider: Send {
+    fn name(&self) -> &'static str;
+
+    // Ok(None) means the provider has no cover art for the metadata,
+    // an error means the lookup itself failed and is worth retrying later
+    fn get_cover_art(&self, metadata: &FileMetadata) -> Result<Option<CoverArt>, &'static str>;
+}
+
+pub fn from_names(names: &[CoverArtProviderName]) -> Vec<Box<dyn CoverArtProvider>> {
+    names.iter()
+         .map(|name| from_name(*name))
+         .collect()
+}
+
+fn from_name(name: CoverArtProviderName) -> Box<dyn CoverArtProvider> {
+    match name {
+        CoverArtProviderName::MusicBrainz => Box::new(MusicBrainzProvider::new()),
+        CoverArtProviderName::ITunes => Box::new(ITunesProvider::new())
+    }
+}
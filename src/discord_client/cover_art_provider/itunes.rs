@@ -0,0 +1,165 @@
+use std::time::Duration;
+use serde::Deserialize;
+use reqwest::blocking::Client;
+use crate::mpv_event_queue::events::FileMetadata;
+use super::{CoverArtProvider, CoverArt};
+
+const BASE_URL: &str = "https://itunes.apple.com";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Artwork URLs point at a 100x100 thumbnail by default,
+// the size is part of the file name and can be swapped out
+const THUMBNAIL_SIZE: &str = "100x100bb";
+const ARTWORK_SIZE: &str = "600x600bb";
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchResult>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResult {
+    artwork_url_100: Option<String>
+}
+
+pub struct ITunesProvider {
+    base_url: String,
+    client: Client
+}
+
+impl ITunesProvider {
+    pub fn new() -> Self {
+        ITunesProvider::with_base_url(BASE_URL)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        let client = Client::builder()
+                            .timeout(REQUEST_TIMEOUT)
+                            .build()
+                            .unwrap_or_default();
+
+        Self {
+            base_url: base_url.to_string(),
+            client
+        }
+    }
+
+    fn get_track_cover_art(&self, artist: &Option<String>, title: &Option<String>) -> Result<Option<String>, &'static str> {
+        match ITunesProvider::get_search_term(artist, title) {
+            Some(term) => self.search(&term, "song"),
+            None => Ok(None)
+        }
+    }
+
+    fn get_album_cover_art(&self, album_artist: &Option<String>, album: &Option<String>) -> Result<Option<String>, &'static str> {
+        match ITunesProvider::get_search_term(album_artist, album) {
+            Some(term) => self.search(&term, "album"),
+            None => Ok(None)
+        }
+    }
+
+    fn get_search_term(artist: &Option<String>, name: &Option<String>) -> Option<String> {
+        let name = name.as_ref()?;
+        match artist {
+            Some(artist) => Some(format!("{artist} {name}")),
+            None => Some(name.clone())
+        }
+    }
+
+    fn search(&self, term: &str, entity: &str) -> Result<Option<String>, &'static str> {
+        let url = format!("{}/search", self.base_url);
+        let request = self.client.get(url)
+                                 .query(&[("term", term), ("entity", entity), ("media", "music"), ("limit", "1")]);
+
+        let response = match request.send().and_then(|response| response.error_for_status()) {
+            Ok(response) => response,
+            Err(_) => return Err("iTunes search request failed")
+        };
+
+        let response: SearchResponse = match response.json() {
+            Ok(response) => response,
+            Err(_) => return Err("invalid iTunes search response")
+        };
+
+        let artwork_url = response.results.into_iter()
+                                          .next()
+                                          .and_then(|result| result.artwork_url_100);
+        Ok(artwork_url.map(|url| url.replace(THUMBNAIL_SIZE, ARTWORK_SIZE)))
+    }
+}
+
+impl CoverArtProvider for ITunesProvider {
+    fn name(&self) -> &'static str {
+        "iTunes"
+    }
+
+    fn get_cover_art(&self, metadata: &FileMetadata) -> Result<Option<CoverArt>, &'static str> {
+        let album_artist = metadata.album_artist.as_ref().or(metadata.artist.as_ref()).cloned();
+
+        let url = match self.get_track_cover_art(&metadata.artist, &metadata.title)? {
+            Some(url) => Some(url),
+            None => self.get_album_cover_art(&album_artist, &metadata.album)?
+        };
+
+        Ok(url.map(|url| CoverArt {
+            url,
+            release_url: None
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mock_server::{MockServer, MockResponse};
+
+    fn metadata() -> FileMetadata {
+        FileMetadata {
+            artist: Some("Artist".to_string()),
+            album: Some("Album".to_string()),
+            title: Some("Title".to_string()),
+            ..FileMetadata::default()
+        }
+    }
+
+    #[test]
+    fn finds_full_size_artwork_of_track() {
+        let server = MockServer::start(vec![
+            ("/search", MockResponse::json(r#"{"results": [{"artworkUrl100": "http://images.example/100x100bb.jpg"}]}"#))
+        ]);
+
+        let cover_art = ITunesProvider::with_base_url(server.url()).get_cover_art(&metadata()).unwrap().unwrap();
+        assert_eq!(cover_art.url, "http://images.example/600x600bb.jpg");
+        assert!(cover_art.release_url.is_none());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("term=Artist+Title"));
+        assert!(requests[0].contains("entity=song"));
+    }
+
+    #[test]
+    fn empty_result_is_not_found() {
+        let server = MockServer::start(vec![
+            ("/search", MockResponse::json(r#"{"results": []}"#))
+        ]);
+
+        assert!(ITunesProvider::with_base_url(server.url()).get_cover_art(&metadata()).unwrap().is_none());
+
+        // The album is searched after the track, by the artist as there is no album artist
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("term=Artist+Album"));
+        assert!(requests[1].contains("entity=album"));
+    }
+
+    #[test]
+    fn http_error_fails_the_lookup() {
+        let server = MockServer::start(vec![
+            ("/search", MockResponse::status(500))
+        ]);
+
+        assert!(ITunesProvider::with_base_url(server.url()).get_cover_art(&metadata()).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Minimal HTTP server for testing the providers without network access.
+// Responses are looked up by path, the query is only recorded
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>
+}
+
+#[derive(Clone)]
+pub struct MockResponse {
+    status: u16,
+    location: Option<String>,
+    body: String
+}
+
+impl MockResponse {
+    pub fn json(body: &str) -> Self {
+        Self {
+            status: 200,
+            location: None,
+            body: body.to_string()
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            location: None,
+            body: String::new()
+        }
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Self {
+            status: 307,
+            location: Some(location.to_string()),
+            body: String::new()
+        }
+    }
+}
+
+impl MockServer {
+    // Paths without a response are answered with 404
+    pub fn start(routes: Vec<(&str, MockResponse)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind mock server");
+        let url = format!("http://{}", listener.local_addr().expect("mock server has no address"));
+        let routes: Vec<(String, MockResponse)> = routes.into_iter()
+                                                        .map(|(path, response)| (path.to_string(), response))
+                                                        .collect();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                MockServer::respond(stream, &routes, &recorded);
+            }
+        });
+
+        Self {
+            url,
+            requests
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // Paths with their query, in the order they were requested
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn respond(mut stream: TcpStream, routes: &[(String, MockResponse)], requests: &Mutex<Vec<String>>) {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+
+        // Requests are GETs without a body, the headers are skipped
+        let mut line = String::new();
+        while reader.read_line(&mut line).is_ok_and(|read| read > 0) && line != "\r\n" {
+            line.clear();
+        }
+
+        let target = request_line.split(' ').nth(1).unwrap_or_default().to_string();
+        let path = target.split('?').next().unwrap_or_default();
+        let response = routes.iter()
+                             .find(|(route, _)| route == path)
+                             .map(|(_, response)| response.clone())
+                             .unwrap_or(MockResponse::status(404));
+        requests.lock().unwrap().push(target);
+
+        let location = match response.location {
+            Some(ref location) => format!("Location: {location}\r\n"),
+            None => String::new()
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{location}Connection: close\r\n\r\n{}",
+            response.status,
+            response.body.len(),
+            response.body
+        );
+    }
+}
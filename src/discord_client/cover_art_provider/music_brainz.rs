@@ -0,0 +1,342 @@
+use std::time::Duration;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use reqwest::StatusCode;
+use reqwest::blocking::Client;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use crate::mpv_event_queue::events::FileMetadata;
+use super::{CoverArtProvider, CoverArt};
+
+const BASE_URL: &str = "https://musicbrainz.org/ws/2";
+const COVER_ART_ARCHIVE_URL: &str = "https://coverartarchive.org";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// MusicBrainz asks for a user agent that identifies the application
+const USER_AGENT: &str = concat!("mpv-rpc/", env!("CARGO_PKG_VERSION"), " ( https://github.com/ryze312/mpv-rpc )");
+
+const MAX_RECORDING_RELEASES: usize = 3;
+pub const RELEASE_PAGE_URL: &str = "https://musicbrainz.org/release";
+const RELEASE_GROUP_PAGE_URL: &str = "https://musicbrainz.org/release-group";
+
+#[derive(Deserialize)]
+struct ReleaseGroupSearch {
+    #[serde(rename = "release-groups")]
+    release_groups: Vec<Entity>
+}
+
+#[derive(Deserialize)]
+struct Recording {
+    #[serde(default)]
+    releases: Vec<Entity>
+}
+
+#[derive(Deserialize)]
+struct Entity {
+    id: String
+}
+
+pub struct MusicBrainzProvider {
+    base_url: String,
+    cover_art_url: String,
+    client: Client
+}
+
+impl MusicBrainzProvider {
+    pub fn new() -> Self {
+        MusicBrainzProvider::with_base_urls(BASE_URL, COVER_ART_ARCHIVE_URL)
+    }
+
+    // Cover Art Archive redirects to the image, which doesn't need to be downloaded,
+    // the redirect itself is used as the URL
+    pub fn with_base_urls(base_url: &str, cover_art_url: &str) -> Self {
+        let client = Client::builder()
+                            .timeout(REQUEST_TIMEOUT)
+                            .user_agent(USER_AGENT)
+                            .redirect(Policy::none())
+                            .build()
+                            .unwrap_or_default();
+
+        Self {
+            base_url: base_url.to_string(),
+            cover_art_url: cover_art_url.to_string(),
+            client
+        }
+    }
+
+    fn has_ids(metadata: &FileMetadata) -> bool {
+        metadata.release_id.is_some() || metadata.release_group_id.is_some() || metadata.recording_id.is_some()
+    }
+
+    // Tagged files are looked up by their IDs only,
+    // a fuzzy search could pick a different release
+    fn get_cover_art_from_ids(&self, metadata: &FileMetadata) -> Result<Option<CoverArt>, &'static str> {
+        if let Some(ref release_id) = metadata.release_id {
+            if let Some(cover_art) = self.get_release_cover_art(release_id)? {
+                return Ok(Some(cover_art));
+            }
+        }
+
+        if let Some(ref release_group_id) = metadata.release_group_id {
+            if let Some(cover_art) = self.get_release_group_cover_art(release_group_id)? {
+                return Ok(Some(cover_art));
+            }
+        }
+
+        match metadata.recording_id {
+            Some(ref recording_id) => self.get_recording_cover_art(recording_id),
+            None => Ok(None)
+        }
+    }
+
+    fn get_release_cover_art(&self, release_id: &str) -> Result<Option<CoverArt>, &'static str> {
+        let url = self.get_front_cover(&format!("release/{release_id}"))?;
+
+        Ok(url.map(|url| CoverArt {
+            url,
+            release_url: Some(format!("{RELEASE_PAGE_URL}/{release_id}"))
+        }))
+    }
+
+    fn get_release_group_cover_art(&self, release_group_id: &str) -> Result<Option<CoverArt>, &'static str> {
+        let url = self.get_front_cover(&format!("release-group/{release_group_id}"))?;
+
+        Ok(url.map(|url| CoverArt {
+            url,
+            release_url: Some(format!("{RELEASE_GROUP_PAGE_URL}/{release_group_id}"))
+        }))
+    }
+
+    fn get_recording_cover_art(&self, recording_id: &str) -> Result<Option<CoverArt>, &'static str> {
+        let url = format!("{}/recording/{recording_id}", self.base_url);
+        let recording: Recording = match self.get_json(&url, &[("inc", "releases")])? {
+            Some(recording) => recording,
+            None => return Ok(None)
+        };
+
+        // Not every release of the recording has cover art uploaded
+        for release in recording.releases.iter().take(MAX_RECORDING_RELEASES) {
+            if let Some(cover_art) = self.get_release_cover_art(&release.id)? {
+                return Ok(Some(cover_art));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn get_track_cover_art(&self, artist: &Option<String>, title: &Option<String>) -> Result<Option<CoverArt>, &'static str> {
+        self.search_release_group(artist, title)
+    }
+
+    fn get_album_cover_art(&self, album_artist: &Option<String>, album: &Option<String>) -> Result<Option<CoverArt>, &'static str> {
+        self.search_release_group(album_artist, album)
+    }
+
+    fn search_release_group(&self, artist: &Option<String>, name: &Option<String>) -> Result<Option<CoverArt>, &'static str> {
+        let query = match MusicBrainzProvider::get_search_query(artist, name) {
+            Some(query) => query,
+            None => return Ok(None)
+        };
+
+        let url = format!("{}/release-group", self.base_url);
+        let search: ReleaseGroupSearch = match self.get_json(&url, &[("query", &query), ("limit", "1")])? {
+            Some(search) => search,
+            None => return Ok(None)
+        };
+
+        match search.release_groups.first() {
+            Some(release_group) => self.get_release_group_cover_art(&release_group.id),
+            None => Ok(None)
+        }
+    }
+
+    // Lucene query, like releasegroup:"Album" AND artist:"Artist"
+    fn get_search_query(artist: &Option<String>, name: &Option<String>) -> Option<String> {
+        let mut terms = vec![format!("releasegroup:{}", MusicBrainzProvider::quote(name.as_ref()?))];
+
+        if let Some(ref artist) = artist {
+            // Some artist fields might contain + characters
+            // Pointing at multiple artists
+            for part in artist.split('+') {
+                terms.push(format!("artist:{}", MusicBrainzProvider::quote(part.trim())));
+            }
+        }
+
+        Some(terms.join(" AND "))
+    }
+
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    // None if the entity doesn't exist
+    fn get_json<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<Option<T>, &'static str> {
+        let request = self.client.get(url)
+                                 .query(query)
+                                 .query(&[("fmt", "json")]);
+
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(_) => return Err("MusicBrainz request failed")
+        };
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => match response.json() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err("invalid MusicBrainz response")
+            },
+            _ => Err("MusicBrainz request failed")
+        }
+    }
+
+    // None if no front cover is uploaded
+    fn get_front_cover(&self, path: &str) -> Result<Option<String>, &'static str> {
+        let url = format!("{}/{path}/front", self.cover_art_url);
+        let response = match self.client.get(&url).send() {
+            Ok(response) => response,
+            Err(_) => return Err("Cover Art Archive request failed")
+        };
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if status.is_redirection() {
+            return match response.headers().get(LOCATION).and_then(|location| location.to_str().ok()) {
+                Some(location) => Ok(Some(location.to_string())),
+                None => Err("Cover Art Archive redirect without a location")
+            };
+        }
+
+        match status.is_success() {
+            true => Ok(Some(url)),
+            false => Err("Cover Art Archive request failed")
+        }
+    }
+}
+
+impl CoverArtProvider for MusicBrainzProvider {
+    fn name(&self) -> &'static str {
+        "MusicBrainz"
+    }
+
+    fn get_cover_art(&self, metadata: &FileMetadata) -> Result<Option<CoverArt>, &'static str> {
+        if MusicBrainzProvider::has_ids(metadata) {
+            return self.get_cover_art_from_ids(metadata);
+        }
+
+        match self.get_track_cover_art(&metadata.artist, &metadata.title)? {
+            Some(cover_art) => Ok(Some(cover_art)),
+            None => self.get_album_cover_art(&metadata.album_artist, &metadata.album)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mock_server::{MockServer, MockResponse};
+
+    fn provider(server: &MockServer) -> MusicBrainzProvider {
+        MusicBrainzProvider::with_base_urls(&format!("{}/ws/2", server.url()), &format!("{}/caa", server.url()))
+    }
+
+    fn untagged() -> FileMetadata {
+        FileMetadata {
+            artist: Some("Artist".to_string()),
+            album_artist: Some("Album Artist".to_string()),
+            album: Some("Album".to_string()),
+            title: Some("Title".to_string()),
+            ..FileMetadata::default()
+        }
+    }
+
+    #[test]
+    fn finds_cover_art_of_searched_release_group() {
+        let server = MockServer::start(vec![
+            ("/ws/2/release-group", MockResponse::json(r#"{"release-groups": [{"id": "group-id"}]}"#)),
+            ("/caa/release-group/group-id/front", MockResponse::redirect("http://images.example/front.jpg"))
+        ]);
+
+        let cover_art = provider(&server).get_cover_art(&untagged()).unwrap().unwrap();
+        assert_eq!(cover_art.url, "http://images.example/front.jpg");
+        assert_eq!(cover_art.release_url.as_deref(), Some("https://musicbrainz.org/release-group/group-id"));
+
+        // The album isn't searched once the track is found
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("query=releasegroup%3A%22Title%22+AND+artist%3A%22Artist%22"));
+        assert!(requests[0].contains("fmt=json"));
+    }
+
+    #[test]
+    fn looks_up_tagged_release_directly() {
+        let server = MockServer::start(vec![
+            ("/caa/release/release-id/front", MockResponse::redirect("http://images.example/release.jpg"))
+        ]);
+        let metadata = FileMetadata {
+            release_id: Some("release-id".to_string()),
+            ..untagged()
+        };
+
+        let cover_art = provider(&server).get_cover_art(&metadata).unwrap().unwrap();
+        assert_eq!(cover_art.url, "http://images.example/release.jpg");
+        assert_eq!(cover_art.release_url.as_deref(), Some("https://musicbrainz.org/release/release-id"));
+        assert_eq!(server.requests(), vec!["/caa/release/release-id/front"]);
+    }
+
+    #[test]
+    fn tries_releases_of_tagged_recording() {
+        let server = MockServer::start(vec![
+            ("/ws/2/recording/recording-id", MockResponse::json(r#"{"releases": [{"id": "first"}, {"id": "second"}]}"#)),
+            ("/caa/release/second/front", MockResponse::redirect("http://images.example/second.jpg"))
+        ]);
+        let metadata = FileMetadata {
+            recording_id: Some("recording-id".to_string()),
+            ..FileMetadata::default()
+        };
+
+        let cover_art = provider(&server).get_cover_art(&metadata).unwrap().unwrap();
+        assert_eq!(cover_art.url, "http://images.example/second.jpg");
+        assert!(server.requests()[0].contains("inc=releases"));
+    }
+
+    #[test]
+    fn empty_search_result_is_not_found() {
+        let server = MockServer::start(vec![
+            ("/ws/2/release-group", MockResponse::json(r#"{"release-groups": []}"#))
+        ]);
+
+        assert!(provider(&server).get_cover_art(&untagged()).unwrap().is_none());
+
+        // Both the track and the album are searched
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("releasegroup%3A%22Album%22+AND+artist%3A%22Album+Artist%22"));
+    }
+
+    #[test]
+    fn missing_front_cover_is_not_found() {
+        let server = MockServer::start(vec![
+            ("/ws/2/release-group", MockResponse::json(r#"{"release-groups": [{"id": "group-id"}]}"#))
+        ]);
+
+        assert!(provider(&server).get_cover_art(&untagged()).unwrap().is_none());
+    }
+
+    #[test]
+    fn http_errors_fail_the_lookup() {
+        let server = MockServer::start(vec![
+            ("/ws/2/release-group", MockResponse::status(503))
+        ]);
+        assert!(provider(&server).get_cover_art(&untagged()).is_err());
+
+        let server = MockServer::start(vec![
+            ("/ws/2/release-group", MockResponse::json(r#"{"release-groups": [{"id": "group-id"}]}"#)),
+            ("/caa/release-group/group-id/front", MockResponse::status(500))
+        ]);
+        assert!(provider(&server).get_cover_art(&untagged()).is_err());
+    }
+}
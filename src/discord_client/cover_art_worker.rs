@@ -0,0 +1,236 @@
+use std::thread;
+use std::sync::mpsc::{self, Sender, Receiver, TryRecvError};
+use crate::mpv_event_queue::events::FileMetadata;
+use super::cover_art_cache::CoverArtCache;
+use super::cover_art_provider::{CoverArtProvider, CoverArt};
+
+pub struct CoverArtRequest {
+    pub id: u64,
+    pub metadata: FileMetadata
+}
+
+pub struct CoverArtResult {
+    pub id: u64,
+    pub cover_art: Option<CoverArt>,
+    pub provider: Option<&'static str>
+}
+
+struct CoverArtLookup {
+    providers: Vec<Box<dyn CoverArtProvider>>,
+    cache: Option<CoverArtCache>
+}
+
+// Cover art lookups go through blocking HTTP clients,
+// so they are done on a separate thread to keep the mpv event loop responsive
+pub struct CoverArtWorker {
+    requests: Sender<CoverArtRequest>,
+    results: Receiver<CoverArtResult>
+}
+
+impl CoverArtWorker {
+    pub fn new(providers: Vec<Box<dyn CoverArtProvider>>, cache: Option<CoverArtCache>) -> Result<Self, &'static str> {
+        let lookup = CoverArtLookup {
+            providers,
+            cache
+        };
+
+        let (request_sender, request_receiver) = mpsc::channel();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let spawned = thread::Builder::new()
+            .name("mpv-rpc-cover-art".to_string())
+            .spawn(move || CoverArtWorker::run(request_receiver, result_sender, lookup));
+
+        match spawned {
+            Ok(_) => Ok(Self {
+                requests: request_sender,
+                results: result_receiver
+            }),
+            Err(_) => Err("cannot spawn cover art worker")
+        }
+    }
+
+    // Like a worker whose thread has died, every request fails
+    #[cfg(test)]
+    pub fn stopped() -> Self {
+        let (requests, _) = mpsc::channel();
+        let (_, results) = mpsc::channel();
+
+        Self {
+            requests,
+            results
+        }
+    }
+
+    fn run(requests: Receiver<CoverArtRequest>, results: Sender<CoverArtResult>, mut lookup: CoverArtLookup) {
+        if let Some(ref mut cache) = lookup.cache {
+            cache.load();
+        }
+
+        // Exits once the sending half is dropped together with the client
+        while let Ok(mut request) = requests.recv() {
+            // Only the latest request matters, skip the ones queued up behind it
+            while let Ok(newer) = requests.try_recv() {
+                request = newer;
+            }
+
+            let result = lookup.get_cover_art(request);
+
+            if results.send(result).is_err() {
+                break;
+            }
+        }
+    }
+
+    pub fn request(&self, request: CoverArtRequest) -> Result<(), &'static str> {
+        match self.requests.send(request) {
+            Ok(()) => Ok(()),
+            Err(_) => Err("cover art worker has stopped")
+        }
+    }
+
+    pub fn next_result(&self) -> Option<CoverArtResult> {
+        match self.results.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => None
+        }
+    }
+}
+
+impl CoverArtLookup {
+    fn get_cover_art(&mut self, request: CoverArtRequest) -> CoverArtResult {
+        let metadata = &request.metadata;
+
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(metadata));
+        if let Some(cover_art) = cached {
+            return CoverArtResult {
+                id: request.id,
+                cover_art,
+                provider: None
+            };
+        }
+
+        // Providers are tried in the configured order, first match wins
+        let mut failed = false;
+        let found = self.providers.iter().find_map(|provider| {
+            match provider.get_cover_art(metadata) {
+                Ok(cover_art) => cover_art.map(|cover_art| (cover_art, provider.name())),
+                Err(_) => {
+                    failed = true;
+                    None
+                }
+            }
+        });
+
+        let (cover_art, provider) = match found {
+            Some((cover_art, provider)) => (Some(cover_art), Some(provider)),
+            None => (None, None)
+        };
+
+        // A failed lookup might have found something, so it isn't cached as a miss.
+        // Otherwise being offline would block cover art until the entry expires
+        if let Some(ref mut cache) = self.cache {
+            if cover_art.is_some() || !failed {
+                cache.insert(metadata, cover_art.clone());
+                cache.save();
+            }
+        }
+
+        CoverArtResult {
+            id: request.id,
+            cover_art,
+            provider
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::TempDir;
+    use super::*;
+
+    struct StubProvider(Result<Option<CoverArt>, &'static str>);
+
+    impl CoverArtProvider for StubProvider {
+        fn name(&self) -> &'static str {
+            "Stub"
+        }
+
+        fn get_cover_art(&self, _: &FileMetadata) -> Result<Option<CoverArt>, &'static str> {
+            self.0.clone()
+        }
+    }
+
+    fn lookup(dir: &TempDir, results: Vec<Result<Option<CoverArt>, &'static str>>) -> CoverArtLookup {
+        let providers = results.into_iter()
+                               .map(|result| Box::new(StubProvider(result)) as Box<dyn CoverArtProvider>)
+                               .collect();
+
+        CoverArtLookup {
+            providers,
+            cache: Some(CoverArtCache::new(dir.file("cache.json")))
+        }
+    }
+
+    fn request() -> CoverArtRequest {
+        CoverArtRequest {
+            id: 1,
+            metadata: FileMetadata {
+                title: Some("Title".to_string()),
+                ..FileMetadata::default()
+            }
+        }
+    }
+
+    fn cover_art(url: &str) -> CoverArt {
+        CoverArt {
+            url: url.to_string(),
+            release_url: None
+        }
+    }
+
+    fn cached(lookup: &CoverArtLookup) -> Option<Option<CoverArt>> {
+        lookup.cache.as_ref().unwrap().get(&request().metadata)
+    }
+
+    #[test]
+    fn first_found_cover_art_is_used_and_cached() {
+        let dir = TempDir::new("cover-art-found");
+        let mut lookup = lookup(&dir, vec![Ok(None), Ok(Some(cover_art("first"))), Ok(Some(cover_art("second")))]);
+        let result = lookup.get_cover_art(request());
+
+        assert_eq!(result.cover_art.map(|cover_art| cover_art.url).as_deref(), Some("first"));
+        assert_eq!(result.provider, Some("Stub"));
+        assert_eq!(cached(&lookup).flatten().map(|cover_art| cover_art.url).as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn missing_cover_art_is_cached() {
+        let dir = TempDir::new("cover-art-missing");
+        let mut lookup = lookup(&dir, vec![Ok(None), Ok(None)]);
+        let result = lookup.get_cover_art(request());
+
+        assert!(result.cover_art.is_none());
+        assert!(matches!(cached(&lookup), Some(None)));
+    }
+
+    #[test]
+    fn failed_lookup_is_not_cached() {
+        let dir = TempDir::new("cover-art-failed");
+        let mut lookup = lookup(&dir, vec![Err("request failed"), Ok(None)]);
+        let result = lookup.get_cover_art(request());
+
+        assert!(result.cover_art.is_none());
+        assert!(cached(&lookup).is_none());
+    }
+
+    #[test]
+    fn failure_after_a_match_is_not_reached() {
+        let dir = TempDir::new("cover-art-match");
+        let mut lookup = lookup(&dir, vec![Ok(Some(cover_art("found"))), Err("request failed")]);
+        lookup.get_cover_art(request());
+
+        assert!(cached(&lookup).flatten().is_some());
+    }
+}
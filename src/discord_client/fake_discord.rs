@@ -0,0 +1,170 @@
+// Stand-in for the Discord client, listening on the same IPC socket.
+// Completes the handshake and records every activity it receives,
+// so the presence can be checked without running Discord
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+const OP_PING: u32 = 3;
+const OP_PONG: u32 = 4;
+
+// Activities are sent without waiting for a reply,
+// they might still be on the way when they are checked
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Recording {
+    client_ids: Vec<String>,
+
+    // Kept to disconnect the clients
+    clients: Vec<UnixStream>,
+
+    // Null for cleared activities
+    activities: Vec<Value>
+}
+
+pub struct FakeDiscord {
+    recording: Arc<Mutex<Recording>>
+}
+
+impl FakeDiscord {
+    // The plugin finds sockets named discord-ipc-0 in directories like XDG_RUNTIME_DIR,
+    // tests give the path to the client instead
+    pub fn bind(socket_path: &Path) -> io::Result<Self> {
+        // Left over from a previous run
+        let _ = fs::remove_file(socket_path);
+        let listener = UnixListener::bind(socket_path)?;
+        let recording = Arc::new(Mutex::new(Recording::default()));
+
+        let clients_recording = Arc::clone(&recording);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Ok(client) = stream.try_clone() {
+                    clients_recording.lock().unwrap().clients.push(client);
+                }
+
+                let recording = Arc::clone(&clients_recording);
+                thread::spawn(move || FakeDiscord::handle_client(stream, &recording));
+            }
+        });
+
+        Ok(Self {
+            recording
+        })
+    }
+
+    // Client IDs from the handshakes, in the order the clients connected
+    pub fn get_client_ids(&self) -> Vec<String> {
+        self.recording.lock().unwrap().client_ids.clone()
+    }
+
+    pub fn get_activities(&self) -> Vec<Value> {
+        self.recording.lock().unwrap().activities.clone()
+    }
+
+    pub fn wait_for_activities(&self, count: usize) -> Vec<Value> {
+        let start = Instant::now();
+        while self.get_activities().len() < count && start.elapsed() < WAIT_TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        self.get_activities()
+    }
+
+    pub fn clear(&self) {
+        let mut recording = self.recording.lock().unwrap();
+        recording.client_ids.clear();
+        recording.activities.clear();
+    }
+
+    // Like Discord quitting, the clients notice once they send something
+    pub fn disconnect(&self) {
+        for client in self.recording.lock().unwrap().clients.drain(..) {
+            let _ = client.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn handle_client(mut stream: UnixStream, recording: &Mutex<Recording>) {
+        // Errors only end the connection, the client notices on its own
+        while let Ok((opcode, payload)) = FakeDiscord::read_frame(&mut stream) {
+            let result = match opcode {
+                OP_HANDSHAKE => {
+                    let client_id = payload["client_id"].as_str().unwrap_or_default().to_string();
+                    recording.lock().unwrap().client_ids.push(client_id);
+                    FakeDiscord::write_frame(&mut stream, OP_FRAME, &FakeDiscord::get_ready())
+                }
+                OP_FRAME => FakeDiscord::handle_command(&mut stream, &payload, recording),
+                OP_PING => FakeDiscord::write_frame(&mut stream, OP_PONG, &payload),
+                OP_CLOSE => break,
+                _ => Ok(())
+            };
+
+            if result.is_err() {
+                break;
+            }
+        }
+    }
+
+    fn get_ready() -> Value {
+        json!({
+            "cmd": "DISPATCH",
+            "evt": "READY",
+            "data": {
+                "v": 1,
+                "user": { "id": "0", "username": "fake-discord" }
+            }
+        })
+    }
+
+    fn handle_command(stream: &mut UnixStream, payload: &Value, recording: &Mutex<Recording>) -> io::Result<()> {
+        if payload["cmd"] != "SET_ACTIVITY" {
+            return Ok(());
+        }
+
+        let activity = payload["args"]["activity"].clone();
+        recording.lock().unwrap().activities.push(activity.clone());
+
+        let response = json!({
+            "cmd": "SET_ACTIVITY",
+            "evt": null,
+            "data": activity,
+            "nonce": payload["nonce"]
+        });
+        FakeDiscord::write_frame(stream, OP_FRAME, &response)
+    }
+
+    // Frames are a little endian opcode and length followed by JSON
+    fn read_frame(stream: &mut UnixStream) -> io::Result<(u32, Value)> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header)?;
+
+        let opcode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        let mut data = vec![0; length as usize];
+        stream.read_exact(&mut data)?;
+
+        match serde_json::from_slice(&data) {
+            Ok(payload) => Ok((opcode, payload)),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+    }
+
+    fn write_frame(stream: &mut UnixStream, opcode: u32, payload: &Value) -> io::Result<()> {
+        let data = payload.to_string();
+
+        stream.write_all(&opcode.to_le_bytes())?;
+        stream.write_all(&(data.len() as u32).to_le_bytes())?;
+        stream.write_all(data.as_bytes())
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+use discord_rich_presence::error::Error;
+use serde_json::json;
+
+const OP_CLOSE: u8 = 2;
+
+// Connects to the socket of Discord, as found through XDG_RUNTIME_DIR and the like,
+// or to a given socket, so the tests don't have to change the environment
+pub enum IpcClient {
+    Discord(DiscordIpcClient),
+    Socket {
+        client_id: String,
+        path: PathBuf,
+        socket: Option<UnixStream>
+    }
+}
+
+impl IpcClient {
+    pub fn new(client_id: &str, socket_path: Option<PathBuf>) -> Self {
+        match socket_path {
+            Some(path) => IpcClient::Socket {
+                client_id: client_id.to_string(),
+                path,
+                socket: None
+            },
+            None => IpcClient::Discord(DiscordIpcClient::new(client_id))
+        }
+    }
+
+    pub fn get_socket_path(&self) -> Option<PathBuf> {
+        match self {
+            IpcClient::Discord(_) => None,
+            IpcClient::Socket { path, .. } => Some(path.clone())
+        }
+    }
+}
+
+// The socket is used the same way DiscordIpcClient does
+impl DiscordIpc for IpcClient {
+    fn connect_ipc(&mut self) -> Result<(), Error> {
+        match self {
+            IpcClient::Discord(discord) => discord.connect_ipc(),
+            IpcClient::Socket { path, socket, .. } => match UnixStream::connect(path) {
+                Ok(stream) => {
+                    *socket = Some(stream);
+                    Ok(())
+                }
+                Err(_) => Err(Error::IPCConnectionFailed)
+            }
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        match self {
+            IpcClient::Discord(discord) => discord.write(data),
+            IpcClient::Socket { socket, .. } => {
+                let socket = socket.as_mut().ok_or(Error::NotConnected)?;
+                socket.write_all(data).map_err(Error::WriteError)
+            }
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        match self {
+            IpcClient::Discord(discord) => discord.read(buffer),
+            IpcClient::Socket { socket, .. } => {
+                let socket = socket.as_mut().ok_or(Error::NotConnected)?;
+                socket.read_exact(buffer).map_err(Error::ReadError)
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        if let IpcClient::Discord(discord) = self {
+            return discord.close();
+        }
+
+        let _ = self.send(json!({}), OP_CLOSE);
+        match self {
+            IpcClient::Socket { socket: Some(socket), .. } => {
+                socket.flush().map_err(Error::FlushError)?;
+                let _ = socket.shutdown(Shutdown::Both);
+                Ok(())
+            }
+            _ => Err(Error::NotConnected)
+        }
+    }
+
+    fn get_client_id(&self) -> &str {
+        match self {
+            IpcClient::Discord(discord) => discord.get_client_id(),
+            IpcClient::Socket { client_id, .. } => client_id
+        }
+    }
+}
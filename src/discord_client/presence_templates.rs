@@ -0,0 +1,261 @@
+use crate::utils;
+use crate::config::{self, TemplatesConfig};
+use crate::template::Template;
+use crate::logging::{self, Logger};
+use crate::mpv_event_queue::events::FileInfo;
+
+const FIELDS: [&str; 18] = [
+    "filename",
+    "artist",
+    "album_artist",
+    "album",
+    "title",
+    "track",
+    "release_id",
+    "release_group_id",
+    "recording_id",
+    "playlist_pos",
+    "playlist_count",
+    "chapter",
+    "duration",
+    "video_title",
+    "season",
+    "episode",
+    "year",
+    "group"
+];
+
+pub struct PresenceTemplates {
+    details: Template,
+    state: Template,
+    large_text: Template
+}
+
+impl PresenceTemplates {
+    pub fn from_config(config: &TemplatesConfig, logger: &Logger) -> Self {
+        Self {
+            details: PresenceTemplates::parse("details", &config.details, config::DETAILS_TEMPLATE, logger),
+            state: PresenceTemplates::parse("state", &config.state, config::STATE_TEMPLATE, logger),
+            large_text: PresenceTemplates::parse("large_text", &config.large_text, config::LARGE_TEXT_TEMPLATE, logger)
+        }
+    }
+
+    // Invalid templates are reported and replaced with the default one
+    fn parse(name: &str, source: &str, default: &str, logger: &Logger) -> Template {
+        match Template::parse(source, &FIELDS) {
+            Ok(template) => template,
+            Err(e) => {
+                logging::error!(logger, "Invalid {name} template \"{source}\" at {e}. Using default template");
+                match Template::parse(default, &FIELDS) {
+                    Ok(template) => template,
+                    Err(_) => unreachable!("default {name} template is invalid")
+                }
+            }
+        }
+    }
+
+    pub fn get_details(&self, file_info: &FileInfo) -> String {
+        PresenceTemplates::render(&self.details, file_info)
+    }
+
+    pub fn get_state(&self, file_info: &FileInfo) -> String {
+        PresenceTemplates::render(&self.state, file_info)
+    }
+
+    pub fn get_large_text(&self, file_info: &FileInfo) -> String {
+        PresenceTemplates::render(&self.large_text, file_info)
+    }
+
+    // Optional parts start with their separator, like "{? S{season}}",
+    // which is left over at the start when the parts before them are empty
+    fn render(template: &Template, file_info: &FileInfo) -> String {
+        let text = template.render(|name| PresenceTemplates::get_value(file_info, name));
+        text.trim().to_string()
+    }
+
+    fn get_value(file_info: &FileInfo, name: &str) -> Option<String> {
+        let metadata = &file_info.metadata;
+        let media_name = file_info.media_name.as_ref();
+        match name {
+            "filename" => Some(file_info.filename.clone()),
+            "artist" => metadata.artist.clone(),
+            "album_artist" => metadata.album_artist.clone(),
+            "album" => metadata.album.clone(),
+            "title" => metadata.title.clone(),
+            "track" => metadata.track.clone(),
+            "release_id" => metadata.release_id.clone(),
+            "release_group_id" => metadata.release_group_id.clone(),
+            "recording_id" => metadata.recording_id.clone(),
+            "playlist_pos" => file_info.playlist_pos.map(|pos| pos.to_string()),
+            "playlist_count" => file_info.playlist_count.map(|count| count.to_string()),
+            "chapter" => file_info.chapter.clone(),
+            "duration" => file_info.duration.map(utils::format_duration),
+            "video_title" => media_name.map(|media_name| media_name.title.clone()),
+            "season" => media_name.and_then(|media_name| media_name.season).map(|season| season.to_string()),
+            "episode" => media_name.and_then(|media_name| media_name.episode).map(|episode| episode.to_string()),
+            "year" => media_name.and_then(|media_name| media_name.year).map(|year| year.to_string()),
+            "group" => media_name.and_then(|media_name| media_name.group.clone()),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogLevel;
+    use crate::media_name::MediaName;
+    use crate::mpv_event_queue::events::FileMetadata;
+    use crate::test_utils;
+
+    fn file_info() -> FileInfo {
+        FileInfo {
+            playlist_pos: Some(2),
+            playlist_count: Some(10),
+            chapter: Some("Chapter".to_string()),
+            duration: Some(3725.0),
+            metadata: FileMetadata {
+                artist: Some("Artist".to_string()),
+                album_artist: Some("Album Artist".to_string()),
+                album: Some("Album".to_string()),
+                title: Some("Title".to_string()),
+                track: Some("7".to_string()),
+                release_id: Some("release".to_string()),
+                release_group_id: Some("release-group".to_string()),
+                recording_id: Some("recording".to_string())
+            },
+            media_name: Some(MediaName {
+                title: "Show".to_string(),
+                season: Some(2),
+                episode: Some(5),
+                year: Some(2019),
+                group: Some("Group".to_string())
+            }),
+            ..empty_file_info()
+        }
+    }
+
+    fn empty_file_info() -> FileInfo {
+        test_utils::file_info("/videos/file.mkv")
+    }
+
+    #[test]
+    fn every_field_has_a_value() {
+        let expected = [
+            ("filename", "file.mkv"),
+            ("artist", "Artist"),
+            ("album_artist", "Album Artist"),
+            ("album", "Album"),
+            ("title", "Title"),
+            ("track", "7"),
+            ("release_id", "release"),
+            ("release_group_id", "release-group"),
+            ("recording_id", "recording"),
+            ("playlist_pos", "2"),
+            ("playlist_count", "10"),
+            ("chapter", "Chapter"),
+            ("duration", "1:02:05"),
+            ("video_title", "Show"),
+            ("season", "2"),
+            ("episode", "5"),
+            ("year", "2019"),
+            ("group", "Group")
+        ];
+
+        assert_eq!(expected.len(), FIELDS.len());
+        let file_info = file_info();
+        for (field, value) in expected {
+            assert!(FIELDS.contains(&field), "{field} isn't a field");
+            assert_eq!(PresenceTemplates::get_value(&file_info, field).as_deref(), Some(value), "value of {field}");
+        }
+    }
+
+    #[test]
+    fn every_field_can_be_used_in_templates() {
+        let file_info = file_info();
+        for field in FIELDS {
+            let template = match Template::parse(&format!("{{{field}}}"), &FIELDS) {
+                Ok(template) => template,
+                Err(e) => panic!("{field} can't be used at {e}")
+            };
+            let value = PresenceTemplates::render(&template, &file_info);
+            assert!(!value.is_empty(), "value of {field}");
+        }
+    }
+
+    #[test]
+    fn only_filename_is_always_known() {
+        let file_info = empty_file_info();
+        for field in FIELDS {
+            let value = PresenceTemplates::get_value(&file_info, field);
+            assert_eq!(value.is_some(), field == "filename", "value of {field}");
+        }
+
+        assert!(PresenceTemplates::get_value(&file_info, "unknown").is_none());
+    }
+
+    #[test]
+    fn default_templates_render() {
+        let templates = PresenceTemplates::from_config(&TemplatesConfig::default(), &Logger::new(LogLevel::None));
+        let file_info = file_info();
+
+        assert_eq!(templates.get_details(&file_info), "Title (2019) [T7]");
+        assert_eq!(templates.get_state(&file_info), "by Artist on Album S2 E5");
+        assert_eq!(templates.get_large_text(&file_info), "Title");
+
+        let file_info = empty_file_info();
+        assert_eq!(templates.get_details(&file_info), "file.mkv");
+        assert_eq!(templates.get_state(&file_info), "");
+    }
+
+    #[test]
+    fn default_state_of_episodes() {
+        let templates = PresenceTemplates::from_config(&TemplatesConfig::default(), &Logger::new(LogLevel::None));
+        let get_state = |season, episode| {
+            let mut file_info = empty_file_info();
+            file_info.media_name = Some(MediaName {
+                title: "Show".to_string(),
+                season,
+                episode,
+                year: None,
+                group: None
+            });
+
+            templates.get_state(&file_info)
+        };
+
+        assert_eq!(get_state(Some(2), Some(5)), "S2 E5");
+        assert_eq!(get_state(Some(2), None), "S2");
+        assert_eq!(get_state(None, Some(5)), "E5");
+        assert_eq!(get_state(None, None), "");
+    }
+
+    #[test]
+    fn default_state_of_music() {
+        let templates = PresenceTemplates::from_config(&TemplatesConfig::default(), &Logger::new(LogLevel::None));
+        let mut file_info = empty_file_info();
+        file_info.metadata.artist = Some("Artist".to_string());
+        assert_eq!(templates.get_state(&file_info), "by Artist");
+
+        file_info.metadata.album = Some("Album".to_string());
+        assert_eq!(templates.get_state(&file_info), "by Artist on Album");
+
+        file_info.metadata.artist = None;
+        assert_eq!(templates.get_state(&file_info), "on Album");
+    }
+
+    #[test]
+    fn invalid_template_falls_back_to_default() {
+        let config = TemplatesConfig {
+            details: "{title".to_string(),
+            state: "{nope}".to_string(),
+            large_text: "{album}".to_string()
+        };
+        let templates = PresenceTemplates::from_config(&config, &Logger::new(LogLevel::None));
+        let file_info = file_info();
+
+        assert_eq!(templates.get_details(&file_info), "Title (2019) [T7]");
+        assert_eq!(templates.get_state(&file_info), "by Artist on Album S2 E5");
+        assert_eq!(templates.get_large_text(&file_info), "Album");
+    }
+}
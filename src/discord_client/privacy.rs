@@ -0,0 +1,304 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use glob::Pattern;
+use regex::Regex;
+use reqwest::Url;
+use crate::config::{PrivacyConfig, PrivacyAction, BlockRule};
+use crate::logging::{self, Logger};
+use crate::mpv_event_queue::events::FileInfo;
+
+enum Rule {
+    Directory(PathBuf),
+    Filename(Pattern),
+    Host(String),
+    Metadata(Regex)
+}
+
+// Decides which files are kept out of the presence,
+// privacy mode applies the same action to every file
+pub struct Privacy {
+    action: PrivacyAction,
+    rules: Vec<Rule>,
+    enabled: bool
+}
+
+impl Privacy {
+    // Invalid rules are reported and skipped
+    pub fn from_config(config: &PrivacyConfig, logger: &Logger) -> Self {
+        let rules = config.blocklist.iter()
+                                    .filter_map(|rule| Privacy::parse_rule(rule, logger))
+                                    .collect();
+
+        Self {
+            action: config.action,
+            rules,
+            enabled: false
+        }
+    }
+
+    fn parse_rule(rule: &BlockRule, logger: &Logger) -> Option<Rule> {
+        match rule {
+            BlockRule::Path(path) => Some(Rule::Directory(Privacy::normalize(&Privacy::expand_home(path)))),
+            BlockRule::Filename(glob) => match Pattern::new(glob) {
+                Ok(pattern) => Some(Rule::Filename(pattern)),
+                Err(e) => {
+                    logging::error!(logger, "Invalid filename glob \"{glob}\" in blocklist: {e}");
+                    None
+                }
+            },
+            BlockRule::Host(host) => Some(Rule::Host(host.trim_start_matches("www.").to_lowercase())),
+            BlockRule::Metadata(regex) => match Regex::new(regex) {
+                Ok(regex) => Some(Rule::Metadata(regex)),
+                Err(e) => {
+                    logging::error!(logger, "Invalid metadata regex \"{regex}\" in blocklist: {e}");
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // None if the file can be published as usual
+    pub fn get_action(&self, file_info: &FileInfo) -> Option<PrivacyAction> {
+        match self.enabled || self.rules.iter().any(|rule| Privacy::matches(rule, file_info)) {
+            true => Some(self.action),
+            false => None
+        }
+    }
+
+    fn matches(rule: &Rule, file_info: &FileInfo) -> bool {
+        match rule {
+            Rule::Directory(directory) => Privacy::get_local_path(&file_info.path).map(|path| path.starts_with(directory))
+                                                                                   .unwrap_or(false),
+            Rule::Filename(pattern) => pattern.matches(&file_info.filename),
+            Rule::Host(host) => Privacy::matches_host(host, &file_info.path),
+            Rule::Metadata(regex) => {
+                let metadata = &file_info.metadata;
+                [&metadata.artist, &metadata.album_artist, &metadata.album, &metadata.title].into_iter()
+                                                                                           .flatten()
+                                                                                           .any(|value| regex.is_match(value))
+            }
+        }
+    }
+
+    // mpv keeps paths as they were given, which can be relative to its working directory
+    // or a file:// URL. Parent directories are resolved, so "private/../public" isn't in "private"
+    fn get_local_path(path: &str) -> Option<PathBuf> {
+        let path = match path.contains("://") {
+            true => Url::parse(path).ok()
+                                    .filter(|url| url.scheme() == "file")?
+                                    .to_file_path()
+                                    .ok()?,
+            false => PathBuf::from(path)
+        };
+
+        match path.is_absolute() {
+            true => Some(Privacy::normalize(&path)),
+            false => Some(Privacy::normalize(&env::current_dir().ok()?.join(path)))
+        }
+    }
+
+    // Only the path itself is looked at, symlinks aren't followed
+    fn normalize(path: &Path) -> PathBuf {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component)
+            }
+        }
+
+        normalized
+    }
+
+    fn matches_host(host: &str, path: &str) -> bool {
+        let url = match Url::parse(path) {
+            Ok(url) => url,
+            Err(_) => return false
+        };
+
+        match url.host_str() {
+            Some(url_host) => {
+                let url_host = url_host.to_lowercase();
+                url_host == host || url_host.ends_with(&format!(".{host}"))
+            }
+            None => false
+        }
+    }
+
+    fn expand_home(path: &str) -> PathBuf {
+        match (path.strip_prefix("~/"), env::var("HOME")) {
+            (Some(path), Ok(home)) => Path::new(&home).join(path),
+            _ => PathBuf::from(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::PrivacyConfig;
+    use crate::logging::LogLevel;
+    use crate::mpv_event_queue::events::FileMetadata;
+    use crate::test_utils;
+    use super::*;
+
+    fn privacy(blocklist: Vec<BlockRule>) -> Privacy {
+        privacy_with_action(PrivacyAction::Hide, blocklist)
+    }
+
+    fn privacy_with_action(action: PrivacyAction, blocklist: Vec<BlockRule>) -> Privacy {
+        let config = PrivacyConfig {
+            action,
+            blocklist
+        };
+
+        Privacy::from_config(&config, &Logger::new(LogLevel::None))
+    }
+
+    fn is_blocked(privacy: &Privacy, path: &str) -> bool {
+        privacy.get_action(&test_utils::file_info(path)).is_some()
+    }
+
+    #[test]
+    fn directory_rule_matches_paths() {
+        let privacy = privacy(vec![BlockRule::Path("/videos/private".to_string())]);
+
+        assert!(is_blocked(&privacy, "/videos/private/a.mkv"));
+        assert!(is_blocked(&privacy, "/videos/private/sub/a.mkv"));
+        assert!(is_blocked(&privacy, "/videos/./private/a.mkv"));
+        assert!(is_blocked(&privacy, "/videos/public/../private/a.mkv"));
+        assert!(!is_blocked(&privacy, "/videos/private/../public/a.mkv"));
+        assert!(!is_blocked(&privacy, "/videos/private-not/a.mkv"));
+        assert!(!is_blocked(&privacy, "/videos/a.mkv"));
+    }
+
+    #[test]
+    fn directory_rule_matches_file_urls() {
+        let privacy = privacy(vec![BlockRule::Path("/videos/private dir".to_string())]);
+
+        assert!(is_blocked(&privacy, "file:///videos/private%20dir/a.mkv"));
+        assert!(is_blocked(&privacy, "file://localhost/videos/private%20dir/a.mkv"));
+        assert!(!is_blocked(&privacy, "file:///videos/private%20dir/../a.mkv"));
+        assert!(!is_blocked(&privacy, "https://example.com/videos/private%20dir/a.mkv"));
+    }
+
+    #[test]
+    fn directory_rule_matches_relative_paths() {
+        let directory = env::current_dir().unwrap().join("private");
+        let privacy = privacy(vec![BlockRule::Path(directory.to_string_lossy().into_owned())]);
+
+        assert!(is_blocked(&privacy, "private/a.mkv"));
+        assert!(is_blocked(&privacy, "./public/../private/a.mkv"));
+        assert!(!is_blocked(&privacy, "private/../a.mkv"));
+    }
+
+    #[test]
+    fn directory_rule_is_normalized() {
+        let privacy = privacy(vec![BlockRule::Path("/videos/public/../private/".to_string())]);
+
+        assert!(is_blocked(&privacy, "/videos/private/a.mkv"));
+        assert!(!is_blocked(&privacy, "/videos/public/a.mkv"));
+    }
+
+    #[test]
+    fn filename_rule_matches_globs() {
+        let privacy = privacy(vec![BlockRule::Filename("*.secret.*".to_string())]);
+
+        assert!(is_blocked(&privacy, "/videos/holiday.secret.mkv"));
+        assert!(is_blocked(&privacy, "https://example.com/holiday.secret.mp4"));
+        assert!(!is_blocked(&privacy, "/videos/holiday.mkv"));
+
+        // Only the file name is matched, not the directories
+        assert!(!is_blocked(&privacy, "/videos/a.secret.dir/holiday.mkv"));
+    }
+
+    #[test]
+    fn invalid_rules_are_skipped() {
+        let privacy = privacy(vec![
+            BlockRule::Filename("[".to_string()),
+            BlockRule::Metadata("(".to_string()),
+            BlockRule::Filename("*.mkv".to_string())
+        ]);
+
+        assert_eq!(privacy.rules.len(), 1);
+        assert!(is_blocked(&privacy, "/videos/holiday.mkv"));
+    }
+
+    #[test]
+    fn host_rule_matches_subdomains() {
+        let privacy = privacy(vec![BlockRule::Host("www.Example.com".to_string())]);
+
+        assert!(is_blocked(&privacy, "https://example.com/watch?v=1"));
+        assert!(is_blocked(&privacy, "https://www.example.com/watch?v=1"));
+        assert!(is_blocked(&privacy, "https://media.EXAMPLE.com/video.mp4"));
+        assert!(is_blocked(&privacy, "http://example.com:8080/stream"));
+        assert!(!is_blocked(&privacy, "https://notexample.com/watch?v=1"));
+        assert!(!is_blocked(&privacy, "https://example.com.evil.org/watch?v=1"));
+
+        // Local files have no host
+        assert!(!is_blocked(&privacy, "/videos/example.com/video.mp4"));
+        assert!(!is_blocked(&privacy, "file:///videos/example.com/video.mp4"));
+    }
+
+    #[test]
+    fn metadata_rule_matches_tags() {
+        let privacy = privacy(vec![BlockRule::Metadata("(?i)guilty pleasure".to_string())]);
+        let file_info = test_utils::song_file_info();
+        assert!(privacy.get_action(&file_info).is_none());
+
+        let tagged: [fn(&mut FileMetadata) -> &mut Option<String>; 4] = [
+            |metadata| &mut metadata.artist,
+            |metadata| &mut metadata.album_artist,
+            |metadata| &mut metadata.album,
+            |metadata| &mut metadata.title
+        ];
+        for get_tag in tagged {
+            let mut file_info = test_utils::song_file_info();
+            *get_tag(&mut file_info.metadata) = Some("Guilty Pleasures".to_string());
+            assert!(privacy.get_action(&file_info).is_some());
+        }
+
+        // Only the tags are matched, not the file name
+        let file_info = test_utils::file_info("/videos/guilty pleasure.mkv");
+        assert!(privacy.get_action(&file_info).is_none());
+    }
+
+    #[test]
+    fn rules_apply_configured_action() {
+        let blocklist = vec![BlockRule::Filename("*.secret.mkv".to_string())];
+        let hide = privacy_with_action(PrivacyAction::Hide, blocklist.clone());
+        let generic = privacy_with_action(PrivacyAction::Generic, blocklist);
+
+        let blocked = test_utils::file_info("/videos/a.secret.mkv");
+        assert!(hide.get_action(&blocked) == Some(PrivacyAction::Hide));
+        assert!(generic.get_action(&blocked) == Some(PrivacyAction::Generic));
+
+        let public = test_utils::file_info("/videos/a.mkv");
+        assert!(hide.get_action(&public).is_none());
+        assert!(generic.get_action(&public).is_none());
+    }
+
+    #[test]
+    fn privacy_mode_applies_to_every_file() {
+        let mut privacy = privacy_with_action(PrivacyAction::Generic, Vec::new());
+        let file_info = test_utils::file_info("/videos/a.mkv");
+        assert!(privacy.get_action(&file_info).is_none());
+
+        privacy.set_enabled(true);
+        assert!(privacy.is_enabled());
+        assert!(privacy.get_action(&file_info) == Some(PrivacyAction::Generic));
+
+        privacy.set_enabled(false);
+        assert!(privacy.get_action(&file_info).is_none());
+    }
+}
@@ -5,15 +5,18 @@ mod logging;
 mod mpv_event_queue;
 mod discord_client;
 mod plugin;
+mod media_name;
+mod template;
 mod utils;
 
-use plugin::RPCPlugin;
+#[cfg(test)]
+mod test_utils;
 
-const DISCORD_APPID: &str = "1071519995588264016";
+use plugin::RPCPlugin;
 
 #[no_mangle]
 fn mpv_open_cplugin(handle: *mut mpv_handle) -> std::os::raw::c_int {
-    let plugin = match RPCPlugin::new(handle, DISCORD_APPID) {
+    let plugin = match RPCPlugin::new(handle) {
         Ok(plugin) => plugin,
         Err(e) => {
             println!("Error creating RPC plugin: {e}");
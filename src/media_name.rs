@@ -0,0 +1,334 @@
+// Show names, episode numbers and movie titles recognised in release file names,
+// like "Show.Name.S02E05.1080p.WEB-DL.x264-GRP.mkv", "Movie.Title.2019.1080p.BluRay.mkv"
+// or anime releases like "[Group] Title - 07v2 (1080p) [ABCD1234].mkv"
+#[derive(Clone, PartialEq)]
+pub struct MediaName {
+    pub title: String,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    pub year: Option<u32>,
+    pub group: Option<String>
+}
+
+// Everything from the first of these on describes the release, not the video
+const NOISE_TOKENS: [&str; 40] = [
+    "4k", "uhd", "hdr", "hdr10", "dv", "sdr",
+    "web", "webrip", "web-dl", "webdl", "bluray", "blu-ray", "bdrip", "brrip", "dvdrip", "hdtv", "remux",
+    "x264", "x265", "h264", "h265", "hevc", "avc", "xvid", "10bit",
+    "aac", "ac3", "dts", "ddp5", "flac",
+    "proper", "repack", "extended", "unrated", "remastered", "internal",
+    "amzn", "nf", "dsnp", "multi"
+];
+
+const MAX_EXTENSION_LEN: usize = 4;
+
+impl MediaName {
+    // Only names with an episode number, a year or release details are recognised,
+    // anything else is most likely a title already
+    pub fn parse(filename: &str) -> Option<Self> {
+        let name = MediaName::strip_extension(filename);
+        if name.starts_with('[') {
+            return MediaName::parse_anime(name);
+        }
+
+        let tokens: Vec<&str> = name.split(|c: char| c == '.' || c == '_' || c.is_whitespace())
+                                    .filter(|token| !token.is_empty())
+                                    .collect();
+
+        let mut title = Vec::new();
+        let mut season = None;
+        let mut episode = None;
+        let mut year = None;
+        let mut recognised = false;
+
+        for (i, token) in tokens.iter().enumerate() {
+            if let Some((token_season, token_episode)) = MediaName::parse_episode(token) {
+                season = token_season;
+                episode = token_episode;
+                recognised = true;
+
+                // The rest is the episode title and release details
+                break;
+            }
+
+            // Numbers in the title, like "Blade Runner 2049 2017", are followed by the actual year
+            let next_is_year = tokens.get(i + 1).and_then(|next| MediaName::parse_year(next)).is_some();
+            match MediaName::parse_year(token) {
+                Some(token_year) if !title.is_empty() && !next_is_year => {
+                    year = year.or(Some(token_year));
+                    recognised = true;
+                    continue;
+                }
+                _ => ()
+            }
+
+            if MediaName::is_noise(token) {
+                recognised = true;
+                break;
+            }
+
+            if !recognised {
+                title.push(*token);
+            }
+        }
+
+        let title = title.join(" ");
+        let title = title.trim_end_matches([' ', '-']);
+        if !recognised || title.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            title: title.to_string(),
+            season,
+            episode,
+            year,
+            group: None
+        })
+    }
+
+    // Fansub releases put the group first and all release details in brackets,
+    // the episode follows the title after a dash
+    fn parse_anime(name: &str) -> Option<Self> {
+        let (group, name) = MediaName::split_bracket(name.strip_prefix('[')?, ']')?;
+        let mut year = None;
+        let mut text = String::new();
+        let mut rest = name;
+
+        // Resolutions, codecs and CRCs are dropped, only a year is kept
+        while let Some(start) = rest.find(['[', '(']) {
+            text.push_str(&rest[..start]);
+
+            let close = match &rest[start..start + 1] {
+                "[" => ']',
+                _ => ')'
+            };
+
+            let (bracket, after) = MediaName::split_bracket(&rest[start + 1..], close)?;
+            year = year.or(MediaName::parse_year(bracket));
+            rest = after;
+        }
+        text.push_str(rest);
+
+        let text = text.replace('_', " ");
+        let text = text.trim();
+        let (title, episode) = match text.rsplit_once(" - ") {
+            Some((title, episode)) => match MediaName::parse_anime_episode(episode) {
+                Some(episode) => (title.trim(), Some(episode)),
+                None => (text, None)
+            },
+            None => (text, None)
+        };
+
+        // Later seasons are often numbered in the title, like "Title S2 - 03"
+        let (title, season) = match title.rsplit_once(' ') {
+            Some((show, season)) => match MediaName::parse_episode(season) {
+                Some((Some(season), None)) => (show.trim(), Some(season)),
+                _ => (title, None)
+            },
+            None => (title, None)
+        };
+
+        if title.is_empty() {
+            return None;
+        }
+
+        let group = group.trim();
+        Some(Self {
+            title: title.to_string(),
+            season,
+            episode,
+            year,
+            group: (!group.is_empty()).then(|| group.to_string())
+        })
+    }
+
+    // 07, 07v2 or 12 END, version suffixes mark re-releases of the same episode
+    fn parse_anime_episode(text: &str) -> Option<u32> {
+        let (episode, rest) = MediaName::split_number(text.trim())?;
+        let rest = rest.trim_start();
+        let is_version = rest.strip_prefix(['v', 'V'])
+                             .map(|version| !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()))
+                             .unwrap_or(false);
+
+        match rest.is_empty() || is_version || rest.eq_ignore_ascii_case("end") {
+            true => Some(episode),
+            false => None
+        }
+    }
+
+    // Text up to the closing bracket and everything after it
+    fn split_bracket(text: &str, close: char) -> Option<(&str, &str)> {
+        let end = text.find(close)?;
+        Some((&text[..end], &text[end + close.len_utf8()..]))
+    }
+
+    // A year at the end, like in "Movie.Title.2019", isn't an extension
+    fn strip_extension(filename: &str) -> &str {
+        match filename.rsplit_once('.') {
+            Some((name, extension)) if extension.len() <= MAX_EXTENSION_LEN
+                                    && extension.chars().all(|c| c.is_ascii_alphanumeric())
+                                    && extension.chars().any(|c| c.is_ascii_alphabetic()) => name,
+            _ => filename
+        }
+    }
+
+    // S02E05, S02E05E06, s2e5, 2x05 and season packs like S02
+    fn parse_episode(token: &str) -> Option<(Option<u32>, Option<u32>)> {
+        let token = token.to_ascii_lowercase();
+
+        if let Some(rest) = token.strip_prefix('s') {
+            let (season, rest) = MediaName::split_number(rest)?;
+            if rest.is_empty() {
+                return Some((Some(season), None));
+            }
+
+            let (episode, _) = MediaName::split_number(rest.strip_prefix('e')?)?;
+            return Some((Some(season), Some(episode)));
+        }
+
+        let (season, rest) = MediaName::split_number(&token)?;
+        let (episode, rest) = MediaName::split_number(rest.strip_prefix('x')?)?;
+        match rest.is_empty() {
+            true => Some((Some(season), Some(episode))),
+            false => None
+        }
+    }
+
+    // Years may be wrapped in brackets, like (2019) or [2019]
+    fn parse_year(token: &str) -> Option<u32> {
+        let token = token.trim_start_matches(['(', '[']).trim_end_matches([')', ']']);
+        if token.len() != 4 {
+            return None;
+        }
+
+        match token.parse() {
+            Ok(year) if (1900..2100).contains(&year) => Some(year),
+            _ => None
+        }
+    }
+
+    // Resolutions like 1080p, and release details with the group appended, like x264-GRP
+    fn is_noise(token: &str) -> bool {
+        let token = token.to_ascii_lowercase();
+        let token = token.trim_start_matches(['(', '[']).trim_end_matches([')', ']']);
+        if MediaName::is_noise_word(token) {
+            return true;
+        }
+
+        match token.split_once('-') {
+            Some((token, _)) => MediaName::is_noise_word(token),
+            None => false
+        }
+    }
+
+    fn is_noise_word(token: &str) -> bool {
+        if let Some(resolution) = token.strip_suffix('p') {
+            if !resolution.is_empty() && resolution.chars().all(|c| c.is_ascii_digit()) {
+                return true;
+            }
+        }
+
+        NOISE_TOKENS.contains(&token)
+    }
+
+    fn split_number(text: &str) -> Option<(u32, &str)> {
+        let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+        let number = text[..end].parse().ok()?;
+
+        Some((number, &text[end..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Title, season, episode, year and group
+    type Expected = Option<(&'static str, Option<u32>, Option<u32>, Option<u32>, Option<&'static str>)>;
+
+    fn check(cases: &[(&str, Expected)]) {
+        for (filename, expected) in cases {
+            let parsed = MediaName::parse(filename);
+            let parsed = parsed.as_ref()
+                               .map(|name| (name.title.as_str(), name.season, name.episode, name.year, name.group.as_deref()));
+
+            assert_eq!(parsed, *expected, "{filename}");
+        }
+    }
+
+    #[test]
+    fn episodes() {
+        check(&[
+            ("Show.Name.S02E05.1080p.WEB-DL.x264-GRP.mkv", Some(("Show Name", Some(2), Some(5), None, None))),
+            ("Show.Name.S02E05.Episode.Title.mkv", Some(("Show Name", Some(2), Some(5), None, None))),
+            ("show_name_s2e5.mp4", Some(("show name", Some(2), Some(5), None, None))),
+            ("Show.Name.S02E05E06.mkv", Some(("Show Name", Some(2), Some(5), None, None))),
+            ("Show Name 2x05 Episode Title.avi", Some(("Show Name", Some(2), Some(5), None, None))),
+            ("Show Name - 2x05.mkv", Some(("Show Name", Some(2), Some(5), None, None))),
+            ("Show.Name.2019.S01E01.mkv", Some(("Show Name", Some(1), Some(1), Some(2019), None)))
+        ]);
+    }
+
+    #[test]
+    fn season_packs() {
+        check(&[
+            ("Show.Name.S02.1080p.BluRay.x264-GRP", Some(("Show Name", Some(2), None, None, None))),
+            ("Show Name S03 Complete.mkv", Some(("Show Name", Some(3), None, None, None)))
+        ]);
+    }
+
+    #[test]
+    fn movies() {
+        check(&[
+            ("Movie.Title.2019.1080p.BluRay.mkv", Some(("Movie Title", None, None, Some(2019), None))),
+            ("Movie Title (2019).mkv", Some(("Movie Title", None, None, Some(2019), None))),
+            ("Movie.Title.1080p.WEB-DL.mkv", Some(("Movie Title", None, None, None, None))),
+            ("Movie.Title.x264-GRP.mkv", Some(("Movie Title", None, None, None, None))),
+            ("Blade.Runner.2049.2017", Some(("Blade Runner 2049", None, None, Some(2017), None))),
+            ("Blade.Runner.2049.2017.2160p.UHD.mkv", Some(("Blade Runner 2049", None, None, Some(2017), None))),
+            ("1917.2019", Some(("1917", None, None, Some(2019), None))),
+            ("1917.2019.1080p.mkv", Some(("1917", None, None, Some(2019), None)))
+        ]);
+    }
+
+    #[test]
+    fn anime() {
+        check(&[
+            ("[Group] Title - 07v2 (1080p) [ABCD1234].mkv", Some(("Title", None, Some(7), None, Some("Group")))),
+            ("[Group] Title - 07 [1080p].mkv", Some(("Title", None, Some(7), None, Some("Group")))),
+            ("[Group] Title S2 - 03 [1080p].mkv", Some(("Title", Some(2), Some(3), None, Some("Group")))),
+            ("[Group] Title - 12 END [720p].mkv", Some(("Title", None, Some(12), None, Some("Group")))),
+            ("[Group] Title (2019) - 05 [1080p].mkv", Some(("Title", None, Some(5), Some(2019), Some("Group")))),
+            ("[Group] Movie Title [2019][BD 1080p].mkv", Some(("Movie Title", None, None, Some(2019), Some("Group")))),
+            ("[Group] 86 - 01 [1080p].mkv", Some(("86", None, Some(1), None, Some("Group")))),
+            ("[Group]_Title_-_07_[1080p].mkv", Some(("Title", None, Some(7), None, Some("Group")))),
+            ("[Group] Title - Part 2 - 04.mkv", Some(("Title - Part 2", None, Some(4), None, Some("Group")))),
+            ("[Group] Title - Special.mkv", Some(("Title - Special", None, None, None, Some("Group")))),
+            ("[] Title - 01.mkv", Some(("Title", None, Some(1), None, None)))
+        ]);
+    }
+
+    #[test]
+    fn unclosed_brackets() {
+        check(&[
+            ("[Group Title - 07.mkv", None),
+            ("[Group] Title - 07 (1080p.mkv", None),
+            ("[Group] Title - 07 [1080p.mkv", None)
+        ]);
+    }
+
+    #[test]
+    fn non_matches() {
+        check(&[
+            ("holiday video.mp4", None),
+            ("holiday_video_2.mp4", None),
+            ("My Song - Live.mkv", None),
+            ("1917.mkv", None),
+            ("2019.mp4", None),
+            ("1080p.mkv", None),
+            ("[Group] [1080p].mkv", None),
+            ("", None)
+        ]);
+    }
+}
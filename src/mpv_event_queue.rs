@@ -1,22 +1,72 @@
 use std::{rc::Rc, time::Duration};
-use mpv_client::{Handle, Event, Property, Format, mpv_handle, ClientMessage};
+use mpv_client::{Handle, mpv_handle};
 use crate::logging::{self, Logger};
+use crate::media_name::MediaName;
 
 pub mod events;
-use events::{MpvEvent, MpvRequest, FileInfo, FileMetadata};
+pub mod commands;
+pub mod handle;
+pub mod scripted_mpv;
+pub mod trace;
 
+use events::{MpvEvent, MpvRequest, FileInfo, FileMetadata, PlaybackTime, MediaKind};
+use handle::{MpvHandle, RawEvent, PropertyFormat, PropertyValue};
+use trace::TracingMpv;
+use commands::Command;
+
+
+// Vorbis comments and ID3 frames name MusicBrainz tags differently
+const KEYS_RELEASE_ID: [&str; 2] = ["MUSICBRAINZ_ALBUMID", "MusicBrainz Album Id"];
+const KEYS_RELEASE_GROUP_ID: [&str; 2] = ["MUSICBRAINZ_RELEASEGROUPID", "MusicBrainz Release Group Id"];
+const KEYS_RECORDING_ID: [&str; 2] = ["MUSICBRAINZ_TRACKID", "MusicBrainz Track Id"];
 
 const NAME_PAUSE_PROP: &str = "pause";
 const REPL_PAUSE_PROP: u64 = 1;
 
+const NAME_BUFFERING_PROP: &str = "paused-for-cache";
+const REPL_BUFFERING_PROP: u64 = 2;
+
+const NAME_CHAPTER_PROP: &str = "chapter";
+const REPL_CHAPTER_PROP: u64 = 3;
+
+// Deliberately not observed, unlike the other stalls. Seeks always end with a PlaybackRestart event,
+// which already publishes the new timestamps, so clearing them when a seek starts
+// would only update the presence twice per seek. Still read to tell when buffering ends
+const NAME_SEEKING_PROP: &str = "seeking";
+
+const NAME_SPEED_PROP: &str = "speed";
+const REPL_SPEED_PROP: u64 = 4;
+
+// Observed without data, the metadata is read again when it changes
+const NAME_METADATA_PROP: &str = "metadata";
+const REPL_METADATA_PROP: u64 = 5;
+
+// Selecting or adding tracks can turn audio into video and back,
+// the media kind is worked out again when any of these change
+const NAME_VIDEO_PROP: &str = "vid";
+const REPL_VIDEO_PROP: u64 = 6;
+
+const NAME_TRACK_LIST_PROP: &str = "track-list";
+const REPL_TRACK_LIST_PROP: u64 = 7;
+
+const NAME_ALBUMART_PROP: &str = "current-tracks/video/albumart";
+const REPL_ALBUMART_PROP: u64 = 8;
+
+// Broadcast along with every OSD message, so scripts sending commands can read the replies
+const REPLY_MESSAGE: &str = "rpc-message";
+
+// Wake up periodically, so results from background work and reconnection attempts are handled
+// even when mpv itself is idle
+const EVENT_WAIT_TIMEOUT: f64 = 0.25;
+
 pub struct MpvEventQueue {
-    mpv: Handle,
+    mpv: Box<dyn MpvHandle>,
     logger: Rc<Logger>
 }
 
 impl MpvEventQueue {
-    pub fn new(mpv: Handle,logger: Rc<Logger>) -> Result<Self, &'static str>  {
-        let new_self = Self {
+    pub fn new(mpv: Box<dyn MpvHandle>, logger: Rc<Logger>) -> Result<Self, &'static str>  {
+        let mut new_self = Self {
             mpv,
             logger,
         };
@@ -25,121 +75,599 @@ impl MpvEventQueue {
         Ok(new_self)
     }
 
-    pub fn from_ptr(handle: *mut mpv_handle, logger: Rc<Logger>) -> Result<Self, &'static str> {
-        MpvEventQueue::new(Handle::from_ptr(handle), logger)
+    // Records a trace of the session, if a trace path is given
+    pub fn from_ptr(handle: *mut mpv_handle, trace_path: Option<&str>, logger: Rc<Logger>) -> Result<Self, &'static str> {
+        let mpv = Box::new(Handle::from_ptr(handle));
+        match trace_path {
+            Some(path) => {
+                logging::info!(logger, "Recording trace to {path}");
+                MpvEventQueue::new(Box::new(TracingMpv::new(mpv, path)?), logger)
+            }
+            None => MpvEventQueue::new(mpv, logger)
+        }
     }
 
-    fn initialize(&self) -> Result<(), &'static str> {
-        self.observe_property(REPL_PAUSE_PROP, NAME_PAUSE_PROP, bool::MPV_FORMAT)
+    // Replays a recorded trace instead of listening to mpv
+    pub fn from_trace(path: &str, logger: Rc<Logger>) -> Result<Self, &'static str> {
+        logging::info!(logger, "Replaying trace from {path}");
+        MpvEventQueue::new(Box::new(trace::load_trace(path)?), logger)
     }
 
-    fn observe_property(&self, id: u64, name: &str, format: i32) -> Result<(), &'static str> {
-        match self.mpv.observe_property(id, name, format) {
-            Ok(_) => Ok(()),
-            Err(_) => Err("cannot observe property")
-        }
+    fn initialize(&mut self) -> Result<(), &'static str> {
+        self.mpv.observe_property(REPL_PAUSE_PROP, NAME_PAUSE_PROP, PropertyFormat::Flag)?;
+        self.mpv.observe_property(REPL_BUFFERING_PROP, NAME_BUFFERING_PROP, PropertyFormat::Flag)?;
+        self.mpv.observe_property(REPL_CHAPTER_PROP, NAME_CHAPTER_PROP, PropertyFormat::Int64)?;
+        self.mpv.observe_property(REPL_SPEED_PROP, NAME_SPEED_PROP, PropertyFormat::Double)?;
+        self.mpv.observe_property(REPL_METADATA_PROP, NAME_METADATA_PROP, PropertyFormat::None)?;
+        self.mpv.observe_property(REPL_VIDEO_PROP, NAME_VIDEO_PROP, PropertyFormat::None)?;
+        self.mpv.observe_property(REPL_TRACK_LIST_PROP, NAME_TRACK_LIST_PROP, PropertyFormat::None)?;
+        self.mpv.observe_property(REPL_ALBUMART_PROP, NAME_ALBUMART_PROP, PropertyFormat::None)
     }
 
     pub fn next_event(&mut self) -> Option<MpvEvent> {
-        let event = self.mpv.wait_event(-1.0);
+        let event = self.mpv.wait_event(EVENT_WAIT_TIMEOUT);
         self.convert_event(event)
     }
 
-    pub fn handle_request(&self, request: MpvRequest) -> Result<(), &'static str> {
+    pub fn handle_request(&mut self, request: MpvRequest) -> Result<(), &'static str> {
         match request {
             MpvRequest::OSDMessage(message) => self.display_osd_message(message)
         }
     }
 
-    pub fn display_osd_message(&self, message: &str) -> Result<(), &'static str> {
-        match self.mpv.osd_message(message, Duration::from_secs(1)) {
-            Ok(()) => Ok(()),
-            Err(_) => Err("cannot print OSD message")
-        }
+    pub fn display_osd_message(&mut self, message: &str) -> Result<(), &'static str> {
+        self.mpv.osd_message(message, Duration::from_secs(1))?;
+        self.mpv.command(&["script-message", REPLY_MESSAGE, message])
     }
 
-    fn convert_event(&self, event: Event) -> Option<MpvEvent> {
+    // Issues stay on screen longer than other messages, there can be several to read
+    pub fn display_config_issues(&mut self, title: &str, issues: &[String]) -> Result<(), &'static str> {
+        let message = [title.to_string()].into_iter()
+                                         .chain(issues.iter().cloned())
+                                         .collect::<Vec<String>>()
+                                         .join("\n");
+        self.mpv.osd_message(&message, Duration::from_secs(5))?;
+        self.mpv.command(&["script-message", REPLY_MESSAGE, &message])
+    }
+
+    pub fn get_script_opts(&self) -> Option<String> {
+        self.mpv.get_property("script-opts")
+    }
+
+    fn convert_event(&self, event: RawEvent) -> Option<MpvEvent> {
         match event {
-            Event::None => (),
+            RawEvent::None => (),
             ref event => logging::info!(self.logger, "Event: {event}")
         }
 
         match event {
-            Event::FileLoaded => self.get_file_info_event(),
-            Event::PlaybackRestart => self.get_seek_event(),
-            Event::ClientMessage(message) => self.get_toggle_event(message),
-            Event::PropertyChange(prop_id, prop) => self.get_property_event(prop_id, prop),
-            Event::Shutdown => Some(MpvEvent::Exit),
+            RawEvent::FileLoaded => self.get_file_info_event(),
+            RawEvent::PlaybackRestart => self.get_seek_event(),
+            RawEvent::ClientMessage(args) => self.get_command_event(args),
+            RawEvent::PropertyChange(prop_id, prop) => self.get_property_event(prop_id, prop),
+            RawEvent::Shutdown => Some(MpvEvent::Exit),
             _ => None
         }
     }
 
     fn get_file_info_event(&self) -> Option<MpvEvent> {
-        let filename = self.mpv.get_property("filename").unwrap();
-        let artist = self.mpv.get_property("metadata/by-key/artist").ok();
-        let album_artist = self.mpv.get_property("metadata/by-key/album_artist").ok();
-        let album = self.mpv.get_property("metadata/by-key/album").ok();
-        let title = self.mpv.get_property("metadata/by-key/title").ok();
-        let track = self.mpv.get_property("metadata/by-key/track").ok();
+        let filename: String = self.mpv.get_property("filename").unwrap_or_default();
+        let path = self.mpv.get_property("path").unwrap_or_default();
+        let playlist_pos = self.mpv.get_property("playlist-pos-1");
+        let playlist_count = self.mpv.get_property("playlist-count");
+        let chapter = self.get_chapter_title();
+        let duration = self.mpv.get_property("duration");
+        let media_kind = self.get_media_kind();
+        let metadata = self.get_file_metadata();
+        let media_name = match (media_kind, &metadata.title) {
+            (MediaKind::Video, None) => MediaName::parse(&filename),
+            _ => None
+        };
 
-        let metadata = FileMetadata {
+        let file_info = FileInfo {
+            filename,
+            path,
+            playlist_pos,
+            playlist_count,
+            chapter,
+            duration,
+            media_kind,
+            metadata,
+            media_name
+        };
+
+        Some(MpvEvent::FileLoaded(Box::new(file_info)))
+    }
+
+    fn get_file_metadata(&self) -> FileMetadata {
+        let mut artist = self.mpv.get_property("metadata/by-key/artist");
+        let album_artist = self.mpv.get_property("metadata/by-key/album_artist");
+        let album = self.mpv.get_property("metadata/by-key/album");
+        let mut title = self.mpv.get_property("metadata/by-key/title");
+        let track = self.mpv.get_property("metadata/by-key/track");
+        let release_id = self.get_metadata_value(&KEYS_RELEASE_ID);
+        let release_group_id = self.get_metadata_value(&KEYS_RELEASE_GROUP_ID);
+        let recording_id = self.get_metadata_value(&KEYS_RECORDING_ID);
+
+        // Internet radio only announces the current song in the stream title
+        let icy_title: Option<String> = self.mpv.get_property("metadata/by-key/icy-title");
+        if let Some(icy_title) = icy_title {
+            let (icy_artist, icy_title) = MpvEventQueue::parse_icy_title(&icy_title);
+            artist = artist.or(icy_artist);
+            title = title.or(icy_title);
+        }
+
+        FileMetadata {
             artist,
             album_artist,
             album,
             title,
-            track
-        };
+            track,
+            release_id,
+            release_group_id,
+            recording_id
+        }
+    }
 
-        let file_info = FileInfo {
-            filename,
-            metadata
-        };
+    // Stations mostly use the "Artist - Title" form
+    fn parse_icy_title(icy_title: &str) -> (Option<String>, Option<String>) {
+        let icy_title = icy_title.trim();
+        if icy_title.is_empty() {
+            return (None, None);
+        }
 
-        Some(MpvEvent::FileLoaded(file_info))
+        match icy_title.split_once(" - ") {
+            Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => {
+                (Some(artist.trim().to_string()), Some(title.trim().to_string()))
+            }
+            _ => (None, Some(icy_title.to_string()))
+        }
     }
 
-    fn get_property_event(&self, prop_id: u64, prop: Property) -> Option<MpvEvent> {
+    fn get_metadata_change_event(&self) -> Option<MpvEvent> {
+        Some(MpvEvent::MetadataChange(Box::new(self.get_file_metadata())))
+    }
+
+    fn get_media_kind_event(&self) -> Option<MpvEvent> {
+        Some(MpvEvent::MediaKindChange(self.get_media_kind()))
+    }
+
+    // Embedded cover art is exposed as a video track, it doesn't make the file a video
+    fn get_media_kind(&self) -> MediaKind {
+        let video: Option<String> = self.mpv.get_property(NAME_VIDEO_PROP);
+        let albumart = self.mpv.get_property(NAME_ALBUMART_PROP).unwrap_or(false);
+
+        match video.as_deref() {
+            None | Some("no") => (),
+            Some(_) if albumart => return MediaKind::Audio,
+            Some(_) => return MediaKind::Video
+        }
+
+        // A video with the video track turned off is still a video
+        match self.has_video_track() {
+            true => MediaKind::Video,
+            false => MediaKind::Audio
+        }
+    }
+
+    fn has_video_track(&self) -> bool {
+        let count: i64 = self.mpv.get_property("track-list/count").unwrap_or_default();
+        (0..count).any(|track| {
+            let track_type: Option<String> = self.mpv.get_property(format!("track-list/{track}/type"));
+            let albumart = self.mpv.get_property(format!("track-list/{track}/albumart")).unwrap_or(false);
+
+            track_type.as_deref() == Some("video") && !albumart
+        })
+    }
+
+    fn get_metadata_value(&self, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| self.mpv.get_property(format!("metadata/by-key/{key}")))
+    }
+
+    fn get_property_event(&self, prop_id: u64, prop: PropertyValue) -> Option<MpvEvent> {
         logging::info!(self.logger, "Property changed: {prop_id}");
-        match prop_id {
-            1 => self.convert_pause_prop(prop.data().unwrap()),
-            2 => self.convert_buffering_prop(prop.data().unwrap()),
+        match (prop_id, prop) {
+            (REPL_PAUSE_PROP, PropertyValue::Flag(pause)) => self.convert_pause_prop(pause),
+            (REPL_BUFFERING_PROP, PropertyValue::Flag(buffering)) => self.convert_buffering_prop(buffering),
+            (REPL_CHAPTER_PROP, _) => self.get_chapter_event(),
+            (REPL_SPEED_PROP, _) => self.get_speed_event(),
+            (REPL_METADATA_PROP, _) => self.get_metadata_change_event(),
+            (REPL_VIDEO_PROP | REPL_TRACK_LIST_PROP | REPL_ALBUMART_PROP, _) => self.get_media_kind_event(),
             _ => None
         }
     }
 
     fn convert_pause_prop(&self, pause: bool) -> Option<MpvEvent> {
-        let time = self.get_remaining_time();
+        let time = self.get_playback_time();
         match pause {
             false => Some(MpvEvent::Play(time)),
             true => Some(MpvEvent::Pause)
         }
     }
 
-    pub fn convert_buffering_prop(&self, buffering: bool) -> Option<MpvEvent> {
-        match buffering {
-            true => Some(MpvEvent::Buffering),
-            false => None
+    // Seeking and waiting for the cache both stall playback,
+    // it only continues once neither of them is happening
+    fn convert_buffering_prop(&self, buffering: bool) -> Option<MpvEvent> {
+        if buffering {
+            return Some(MpvEvent::Buffering);
         }
+
+        if self.is_stalled() {
+            return None;
+        }
+
+        Some(MpvEvent::BufferingEnd(self.get_playback_time()))
+    }
+
+    fn is_stalled(&self) -> bool {
+        let buffering = self.mpv.get_property(NAME_BUFFERING_PROP).unwrap_or(false);
+        let seeking = self.mpv.get_property(NAME_SEEKING_PROP).unwrap_or(false);
+
+        buffering || seeking
+    }
+
+    fn get_chapter_event(&self) -> Option<MpvEvent> {
+        Some(MpvEvent::Chapter(self.get_chapter_title()))
+    }
+
+    fn get_chapter_title(&self) -> Option<String> {
+        self.mpv.get_property("chapter-metadata/title")
     }
 
+    // A seek into an unbuffered part of a stream keeps waiting for the cache,
+    // the timestamps are restored once buffering ends
     fn get_seek_event(&self) -> Option<MpvEvent> {
-        Some(MpvEvent::Seek(self.get_remaining_time()))
+        if self.mpv.get_property(NAME_BUFFERING_PROP).unwrap_or(false) {
+            return None;
+        }
+
+        Some(MpvEvent::Seek(self.get_playback_time()))
+    }
+
+    fn get_speed_event(&self) -> Option<MpvEvent> {
+        Some(MpvEvent::SpeedChange(self.get_playback_time()))
+    }
+
+    // Unlike time-remaining, playtime-remaining accounts for the playback speed
+    fn get_playback_time(&self) -> PlaybackTime {
+        let remaining: Option<f64> = self.mpv.get_property("playtime-remaining");
+        let position: f64 = self.mpv.get_property("time-pos").unwrap_or_default();
+
+        PlaybackTime {
+            remaining: remaining.map(|remaining| remaining.round() as i64),
+            position: position.round() as i64
+        }
+    }
+
+    fn get_command_event(&self, args: Vec<String>) -> Option<MpvEvent> {
+        logging::info!(self.logger, "Client message: {}", args.join(" "));
+
+        match Command::parse(&args)? {
+            Ok(command) => Some(MpvEvent::Command(command)),
+            Err(e) => {
+                logging::error!(self.logger, "Invalid command: {e}");
+                Some(MpvEvent::InvalidCommand(e))
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use crate::logging::LogLevel;
+    use super::*;
+    use super::scripted_mpv::ScriptedMpv;
+
+    // The queue owns its handle, this keeps the script reachable for checking what the queue did
+    struct SharedMpv(Rc<RefCell<ScriptedMpv>>);
+
+    impl MpvHandle for SharedMpv {
+        fn wait_event(&mut self, timeout: f64) -> RawEvent {
+            self.0.borrow_mut().wait_event(timeout)
+        }
+
+        fn observe_property(&mut self, id: u64, name: &str, format: PropertyFormat) -> Result<(), &'static str> {
+            self.0.borrow_mut().observe_property(id, name, format)
+        }
+
+        fn get_property_value(&self, name: &str, format: PropertyFormat) -> Option<PropertyValue> {
+            self.0.borrow().get_property_value(name, format)
+        }
+
+        fn osd_message(&mut self, text: &str, duration: Duration) -> Result<(), &'static str> {
+            self.0.borrow_mut().osd_message(text, duration)
+        }
+
+        fn command(&mut self, args: &[&str]) -> Result<(), &'static str> {
+            self.0.borrow_mut().command(args)
+        }
+    }
+
+    fn start(mpv: ScriptedMpv) -> (MpvEventQueue, Rc<RefCell<ScriptedMpv>>) {
+        let mpv = Rc::new(RefCell::new(mpv));
+        let logger = Rc::new(Logger::new(LogLevel::None));
+        let queue = MpvEventQueue::new(Box::new(SharedMpv(Rc::clone(&mpv))), logger).unwrap();
+
+        (queue, mpv)
     }
 
-    fn get_remaining_time(&self) -> i64 {
-        self.mpv.get_property("time-remaining").unwrap_or_default()
+    fn string(value: &str) -> PropertyValue {
+        PropertyValue::String(value.to_string())
     }
 
-    fn get_toggle_event(&self, message: ClientMessage) -> Option<MpvEvent> {
-        let command = message.args().join(" ");
-        logging::info!(self.logger, "Client message: {command}");
+    fn push_tagged_file(mpv: &mut ScriptedMpv) {
+        mpv.push_property("filename", string("01 Song.flac"));
+        mpv.push_property("path", string("/music/Album/01 Song.flac"));
+        mpv.push_property("playlist-pos-1", PropertyValue::Int64(1));
+        mpv.push_property("playlist-count", PropertyValue::Int64(10));
+        mpv.push_property("duration", PropertyValue::Double(241.5));
+        mpv.push_property("metadata/by-key/artist", string("Artist"));
+        mpv.push_property("metadata/by-key/album_artist", string("Album Artist"));
+        mpv.push_property("metadata/by-key/album", string("Album"));
+        mpv.push_property("metadata/by-key/title", string("Song"));
+        mpv.push_property("metadata/by-key/track", string("1"));
+        mpv.push_property("metadata/by-key/MUSICBRAINZ_ALBUMID", string("release"));
+        mpv.push_property("metadata/by-key/MusicBrainz Release Group Id", string("release-group"));
+        mpv.push_property("metadata/by-key/MUSICBRAINZ_TRACKID", string("recording"));
+    }
+
+    fn push_playback_time(mpv: &mut ScriptedMpv, remaining: f64, position: f64) {
+        mpv.push_property("playtime-remaining", PropertyValue::Double(remaining));
+        mpv.push_property("time-pos", PropertyValue::Double(position));
+    }
 
-        if command.starts_with("key-binding toggle-rpc d-") {
-            Some(MpvEvent::Toggle)
+    fn next_file_info(queue: &mut MpvEventQueue) -> FileInfo {
+        match queue.next_event() {
+            Some(MpvEvent::FileLoaded(file_info)) => *file_info,
+            _ => panic!("expected file loaded event")
         }
-        else {
-            None
+    }
+
+    fn next_media_kind(queue: &mut MpvEventQueue) -> MediaKind {
+        match queue.next_event() {
+            Some(MpvEvent::FileLoaded(file_info)) => file_info.media_kind,
+            Some(MpvEvent::MediaKindChange(media_kind)) => media_kind,
+            _ => panic!("expected media kind")
         }
     }
 
+    fn assert_time(time: PlaybackTime, remaining: Option<i64>, position: i64) {
+        assert_eq!(time.remaining, remaining);
+        assert_eq!(time.position, position);
+    }
+
+    #[test]
+    fn observes_properties() {
+        let (_queue, mpv) = start(ScriptedMpv::new());
+
+        let mpv = mpv.borrow();
+        let observed: Vec<(u64, &str)> = mpv.observed()
+                                            .iter()
+                                            .map(|(id, name)| (*id, name.as_str()))
+                                            .collect();
+        assert_eq!(observed, vec![
+            (REPL_PAUSE_PROP, "pause"),
+            (REPL_BUFFERING_PROP, "paused-for-cache"),
+            (REPL_CHAPTER_PROP, "chapter"),
+            (REPL_SPEED_PROP, "speed"),
+            (REPL_METADATA_PROP, "metadata"),
+            (REPL_VIDEO_PROP, "vid"),
+            (REPL_TRACK_LIST_PROP, "track-list"),
+            (REPL_ALBUMART_PROP, "current-tracks/video/albumart")
+        ]);
+    }
+
+    #[test]
+    fn file_loaded_with_tags() {
+        let mut mpv = ScriptedMpv::new();
+        push_tagged_file(&mut mpv);
+        mpv.push_property("chapter-metadata/title", string("Intro"));
+        mpv.push_event(RawEvent::FileLoaded);
+        let (mut queue, _) = start(mpv);
+
+        let file_info = next_file_info(&mut queue);
+        assert_eq!(file_info.filename, "01 Song.flac");
+        assert_eq!(file_info.path, "/music/Album/01 Song.flac");
+        assert_eq!(file_info.playlist_pos, Some(1));
+        assert_eq!(file_info.playlist_count, Some(10));
+        assert_eq!(file_info.chapter.as_deref(), Some("Intro"));
+        assert_eq!(file_info.duration, Some(241.5));
+        assert!(file_info.media_kind == MediaKind::Audio);
+        assert!(file_info.media_name.is_none());
+
+        let metadata = FileMetadata {
+            artist: Some("Artist".to_string()),
+            album_artist: Some("Album Artist".to_string()),
+            album: Some("Album".to_string()),
+            title: Some("Song".to_string()),
+            track: Some("1".to_string()),
+            release_id: Some("release".to_string()),
+            release_group_id: Some("release-group".to_string()),
+            recording_id: Some("recording".to_string())
+        };
+        assert!(file_info.metadata == metadata);
+    }
+
+    #[test]
+    fn file_loaded_with_icy_title() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_property("filename", string("stream"));
+        mpv.push_property("metadata/by-key/icy-title", string(" Artist - Song "));
+        mpv.push_event(RawEvent::FileLoaded);
+        mpv.push_property("metadata/by-key/icy-title", string("Station Jingle"));
+        mpv.push_event(RawEvent::PropertyChange(REPL_METADATA_PROP, PropertyValue::None));
+        let (mut queue, _) = start(mpv);
+
+        let metadata = next_file_info(&mut queue).metadata;
+        assert_eq!(metadata.artist.as_deref(), Some("Artist"));
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+
+        let Some(MpvEvent::MetadataChange(metadata)) = queue.next_event() else {
+            panic!("expected metadata change event");
+        };
+        assert_eq!(metadata.artist, None);
+        assert_eq!(metadata.title.as_deref(), Some("Station Jingle"));
+    }
+
+    #[test]
+    fn untagged_video_name_is_parsed() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_property("filename", string("Show.S02E05.1080p.mkv"));
+        mpv.push_property("vid", string("1"));
+        mpv.push_event(RawEvent::FileLoaded);
+        let (mut queue, _) = start(mpv);
+
+        let file_info = next_file_info(&mut queue);
+        assert!(file_info.media_kind == MediaKind::Video);
+
+        let media_name = file_info.media_name.expect("expected media name");
+        assert_eq!(media_name.title, "Show");
+        assert_eq!(media_name.season, Some(2));
+        assert_eq!(media_name.episode, Some(5));
+    }
+
+    #[test]
+    fn pause_toggles_playback() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(true)));
+        push_playback_time(&mut mpv, 99.6, 20.4);
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(false)));
+        let (mut queue, _) = start(mpv);
+
+        assert!(matches!(queue.next_event(), Some(MpvEvent::Pause)));
+
+        let Some(MpvEvent::Play(time)) = queue.next_event() else {
+            panic!("expected play event");
+        };
+        assert_time(time, Some(100), 20);
+    }
+
+    #[test]
+    fn live_stream_has_no_remaining_time() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_property("time-pos", PropertyValue::Double(65.0));
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(false)));
+        let (mut queue, _) = start(mpv);
+
+        let Some(MpvEvent::Play(time)) = queue.next_event() else {
+            panic!("expected play event");
+        };
+        assert_time(time, None, 65);
+    }
+
+    #[test]
+    fn playback_restart_is_seek() {
+        let mut mpv = ScriptedMpv::new();
+        push_playback_time(&mut mpv, 50.0, 70.0);
+        mpv.push_event(RawEvent::PlaybackRestart);
+        mpv.push_property(NAME_BUFFERING_PROP, PropertyValue::Flag(true));
+        mpv.push_event(RawEvent::PlaybackRestart);
+        let (mut queue, _) = start(mpv);
+
+        let Some(MpvEvent::Seek(time)) = queue.next_event() else {
+            panic!("expected seek event");
+        };
+        assert_time(time, Some(50), 70);
+
+        // Still waiting for the cache, buffering ending restores the timestamps
+        assert!(queue.next_event().is_none());
+    }
+
+    #[test]
+    fn buffering_ends_once_seeking_ends() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_property(NAME_BUFFERING_PROP, PropertyValue::Flag(true));
+        mpv.push_event(RawEvent::PropertyChange(REPL_BUFFERING_PROP, PropertyValue::Flag(true)));
+        mpv.push_property(NAME_BUFFERING_PROP, PropertyValue::Flag(false));
+        mpv.push_property(NAME_SEEKING_PROP, PropertyValue::Flag(true));
+        mpv.push_event(RawEvent::PropertyChange(REPL_BUFFERING_PROP, PropertyValue::Flag(false)));
+        mpv.push_property(NAME_SEEKING_PROP, PropertyValue::Flag(false));
+        push_playback_time(&mut mpv, 30.0, 90.0);
+        mpv.push_event(RawEvent::PropertyChange(REPL_BUFFERING_PROP, PropertyValue::Flag(false)));
+        let (mut queue, _) = start(mpv);
+
+        assert!(matches!(queue.next_event(), Some(MpvEvent::Buffering)));
+        assert!(queue.next_event().is_none());
+
+        let Some(MpvEvent::BufferingEnd(time)) = queue.next_event() else {
+            panic!("expected buffering end event");
+        };
+        assert_time(time, Some(30), 90);
+    }
+
+    #[test]
+    fn speed_change_updates_time() {
+        let mut mpv = ScriptedMpv::new();
+        push_playback_time(&mut mpv, 40.2, 120.0);
+        mpv.push_event(RawEvent::PropertyChange(REPL_SPEED_PROP, PropertyValue::Double(2.0)));
+        let (mut queue, _) = start(mpv);
+
+        let Some(MpvEvent::SpeedChange(time)) = queue.next_event() else {
+            panic!("expected speed change event");
+        };
+        assert_time(time, Some(40), 120);
+    }
+
+    #[test]
+    fn cover_art_is_audio() {
+        let mut mpv = ScriptedMpv::new();
+        push_tagged_file(&mut mpv);
+        mpv.push_property("vid", string("1"));
+        mpv.push_property(NAME_ALBUMART_PROP, PropertyValue::Flag(true));
+        mpv.push_event(RawEvent::FileLoaded);
+
+        // Adding a real video track to the cover art
+        mpv.push_property(NAME_ALBUMART_PROP, PropertyValue::Flag(false));
+        mpv.push_event(RawEvent::PropertyChange(REPL_ALBUMART_PROP, PropertyValue::None));
+
+        // Turning the video track off doesn't make it audio
+        mpv.push_property("vid", string("no"));
+        mpv.push_property("track-list/count", PropertyValue::Int64(3));
+        mpv.push_property("track-list/0/type", string("video"));
+        mpv.push_property("track-list/0/albumart", PropertyValue::Flag(true));
+        mpv.push_property("track-list/1/type", string("audio"));
+        mpv.push_property("track-list/2/type", string("video"));
+        mpv.push_event(RawEvent::PropertyChange(REPL_VIDEO_PROP, PropertyValue::None));
+
+        // Only the cover art is left
+        mpv.push_property("track-list/count", PropertyValue::Int64(2));
+        mpv.push_event(RawEvent::PropertyChange(REPL_TRACK_LIST_PROP, PropertyValue::None));
+        let (mut queue, _) = start(mpv);
+
+        assert!(next_media_kind(&mut queue) == MediaKind::Audio);
+        assert!(next_media_kind(&mut queue) == MediaKind::Video);
+        assert!(next_media_kind(&mut queue) == MediaKind::Video);
+        assert!(next_media_kind(&mut queue) == MediaKind::Audio);
+    }
+
+    #[test]
+    fn client_messages_are_commands() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_event(RawEvent::ClientMessage(vec!["rpc-privacy".to_string(), "on".to_string()]));
+        mpv.push_event(RawEvent::ClientMessage(vec!["rpc-privacy".to_string()]));
+        mpv.push_event(RawEvent::ClientMessage(vec!["privacy".to_string(), "on".to_string()]));
+        let (mut queue, _) = start(mpv);
+
+        assert!(matches!(queue.next_event(), Some(MpvEvent::Command(Command::Privacy(true)))));
+        assert!(matches!(queue.next_event(), Some(MpvEvent::InvalidCommand(e)) if e.starts_with("rpc-privacy takes")));
+        assert!(queue.next_event().is_none());
+        assert!(matches!(queue.next_event(), Some(MpvEvent::Exit)));
+    }
+
+    #[test]
+    fn osd_messages_are_broadcast() {
+        let (mut queue, mpv) = start(ScriptedMpv::new());
+
+        queue.handle_request(MpvRequest::OSDMessage("Discord RPC enabled")).unwrap();
+        queue.display_config_issues("Config issues", &["first".to_string(), "second".to_string()]).unwrap();
+
+        let mpv = mpv.borrow();
+        assert_eq!(mpv.osd_messages(), ["Discord RPC enabled", "Config issues\nfirst\nsecond"]);
+        assert_eq!(mpv.commands(), [
+            vec!["script-message", "rpc-message", "Discord RPC enabled"],
+            vec!["script-message", "rpc-message", "Config issues\nfirst\nsecond"]
+        ]);
+    }
 }
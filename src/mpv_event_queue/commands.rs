@@ -0,0 +1,144 @@
+// Commands sent with script-message-to, like
+//   script-message-to libmpv_rpc rpc-set-details "Watching with friends"
+// and the key bindings, which are sent as client messages as well.
+// Commands can be broadcast with script-message too, so they are prefixed
+// to not be mistaken for the messages of other scripts
+
+// Every error lists the commands, the OSD is the only place they are shown
+macro_rules! usage {
+    ($error:literal) => {
+        concat!($error, "\n",
+                "Commands: rpc-enable, rpc-disable, rpc-toggle, rpc-refresh, ",
+                "rpc-set-details <text>, rpc-set-state <text>, rpc-clear-override, ",
+                "rpc-privacy on|off|toggle, rpc-reload-config")
+    };
+}
+
+pub enum Command {
+    Enable,
+    Disable,
+    Toggle,
+    Refresh,
+    SetDetails(String),
+    SetState(String),
+    ClearOverride,
+    Privacy(bool),
+    TogglePrivacy,
+    ReloadConfig
+}
+
+impl Command {
+    // Messages that aren't commands of the plugin are ignored,
+    // other scripts broadcast their own messages to every client
+    pub fn parse(args: &[String]) -> Option<Result<Self, &'static str>> {
+        let (name, args) = args.split_first()?;
+        let command = match (name.as_str(), args) {
+            ("key-binding", args) => return Command::parse_key_binding(args).map(Ok),
+            ("rpc-enable", []) => Ok(Command::Enable),
+            ("rpc-disable", []) => Ok(Command::Disable),
+            ("rpc-toggle", []) => Ok(Command::Toggle),
+            ("rpc-refresh", []) => Ok(Command::Refresh),
+            ("rpc-set-details", text) => Command::parse_text(text).map(Command::SetDetails),
+            ("rpc-set-state", text) => Command::parse_text(text).map(Command::SetState),
+            ("rpc-clear-override", []) => Ok(Command::ClearOverride),
+            ("rpc-privacy", [state]) => Command::parse_privacy(state),
+            ("rpc-privacy", _) => Err(usage!("rpc-privacy takes on, off or toggle")),
+            ("rpc-reload-config", []) => Ok(Command::ReloadConfig),
+            ("rpc-enable" | "rpc-disable" | "rpc-toggle" | "rpc-refresh" | "rpc-clear-override" | "rpc-reload-config", _) => {
+                Err(usage!("Command doesn't take arguments"))
+            }
+            // The plugin receives its own replies
+            (name, _) if name == super::REPLY_MESSAGE => return None,
+            (name, _) if name.starts_with("rpc-") => Err(usage!("Unknown command")),
+            _ => return None
+        };
+
+        Some(command)
+    }
+
+    // Only reacts to the key being pressed down
+    fn parse_key_binding(args: &[String]) -> Option<Self> {
+        match args {
+            [name, state, ..] if state.starts_with('d') => match name.as_str() {
+                "toggle-rpc" => Some(Command::Toggle),
+                "toggle-privacy" => Some(Command::TogglePrivacy),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    // Quoting is optional, the arguments are joined back together
+    fn parse_text(args: &[String]) -> Result<String, &'static str> {
+        let text = args.join(" ");
+        match text.trim().is_empty() {
+            true => Err(usage!("Text can't be empty")),
+            false => Ok(text.trim().to_string())
+        }
+    }
+
+    fn parse_privacy(state: &str) -> Result<Self, &'static str> {
+        match state {
+            "on" => Ok(Command::Privacy(true)),
+            "off" => Ok(Command::Privacy(false)),
+            "toggle" => Ok(Command::TogglePrivacy),
+            _ => Err(usage!("rpc-privacy takes on, off or toggle"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<Result<Command, &'static str>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Command::parse(&args)
+    }
+
+    #[test]
+    fn commands_are_prefixed() {
+        assert!(matches!(parse(&["rpc-toggle"]), Some(Ok(Command::Toggle))));
+        assert!(matches!(parse(&["rpc-privacy", "toggle"]), Some(Ok(Command::TogglePrivacy))));
+        assert!(matches!(parse(&["rpc-reload-config"]), Some(Ok(Command::ReloadConfig))));
+
+        // Messages of other scripts
+        assert!(parse(&["toggle"]).is_none());
+        assert!(parse(&["refresh"]).is_none());
+        assert!(parse(&[super::super::REPLY_MESSAGE, "Discord RPC enabled"]).is_none());
+    }
+
+    #[test]
+    fn text_arguments_are_joined() {
+        let Some(Ok(Command::SetDetails(text))) = parse(&["rpc-set-details", "Watching", " with friends "]) else {
+            panic!("expected set details command");
+        };
+        assert_eq!(text, "Watching  with friends");
+    }
+
+    #[test]
+    fn errors_list_commands() {
+        let invalid = [
+            parse(&["rpc-unknown"]),
+            parse(&["rpc-refresh", "now"]),
+            parse(&["rpc-set-state", " "]),
+            parse(&["rpc-privacy"]),
+            parse(&["rpc-privacy", "maybe"])
+        ];
+
+        for result in invalid {
+            let Some(Err(e)) = result else {
+                panic!("expected invalid command");
+            };
+            assert!(e.contains("rpc-set-details <text>"), "usage in {e}");
+            assert!(e.contains("rpc-privacy on|off|toggle"), "usage in {e}");
+        }
+    }
+
+    #[test]
+    fn key_bindings_react_to_key_down() {
+        assert!(matches!(parse(&["key-binding", "toggle-rpc", "d-"]), Some(Ok(Command::Toggle))));
+        assert!(matches!(parse(&["key-binding", "toggle-privacy", "d-"]), Some(Ok(Command::TogglePrivacy))));
+        assert!(parse(&["key-binding", "toggle-rpc", "u-"]).is_none());
+    }
+}
@@ -1,24 +1,62 @@
+use crate::media_name::MediaName;
+use super::commands::Command;
+
 pub struct FileInfo {
     pub filename: String,
-    pub metadata: FileMetadata
+    pub path: String,
+    pub playlist_pos: Option<i64>,
+    pub playlist_count: Option<i64>,
+    pub chapter: Option<String>,
+    pub duration: Option<f64>,
+    pub media_kind: MediaKind,
+    pub metadata: FileMetadata,
+
+    // Recognised from the file name of untagged videos
+    pub media_name: Option<MediaName>
+}
+
+// Audio files with embedded cover art count as audio
+#[derive(Clone, Copy, PartialEq)]
+pub enum MediaKind {
+    Audio,
+    Video
 }
 
+#[derive(Clone, PartialEq, Default)]
 pub struct FileMetadata {
     pub artist: Option<String>,
     pub album_artist: Option<String>,
     pub album: Option<String>,
     pub title: Option<String>,
-    pub track: Option<String>
+    pub track: Option<String>,
+
+    // MusicBrainz identifiers, as tagged by Picard
+    pub release_id: Option<String>,
+    pub release_group_id: Option<String>,
+    pub recording_id: Option<String>
+}
+
+// Remaining time is unknown for live streams
+#[derive(Clone, Copy)]
+pub struct PlaybackTime {
+    pub remaining: Option<i64>,
+    pub position: i64
 }
 
 pub enum MpvEvent {
-    Toggle,
+    Command(Command),
+    InvalidCommand(&'static str),
     Buffering,
+    BufferingEnd(PlaybackTime),
     Exit,
-    FileLoaded(FileInfo),
-    Play(i64),
+    FileLoaded(Box<FileInfo>),
+    MetadataChange(Box<FileMetadata>),
+    Chapter(Option<String>),
+    Play(PlaybackTime),
     Pause,
-    Seek(i64)
+    Seek(PlaybackTime),
+    SpeedChange(PlaybackTime),
+    MediaKindChange(MediaKind)
 }
 
 pub enum MpvRequest {
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use mpv_client::{Handle, Event, Property, Format};
+
+#[derive(Clone, Copy)]
+pub enum PropertyFormat {
+    None,
+    Flag,
+    Int64,
+    Double,
+    String
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PropertyValue {
+    None,
+    Flag(bool),
+    Int64(i64),
+    Double(f64),
+    String(String)
+}
+
+// Owned counterpart of mpv_client::Event, limited to the events the plugin uses
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RawEvent {
+    None,
+    Shutdown,
+    FileLoaded,
+    PlaybackRestart,
+    ClientMessage(Vec<String>),
+    PropertyChange(u64, PropertyValue),
+    Other(String)
+}
+
+// Operations the event queue needs from mpv,
+// so the conversion logic doesn't depend on a live player
+pub trait MpvHandle {
+    fn wait_event(&mut self, timeout: f64) -> RawEvent;
+    fn observe_property(&mut self, id: u64, name: &str, format: PropertyFormat) -> Result<(), &'static str>;
+    fn get_property_value(&self, name: &str, format: PropertyFormat) -> Option<PropertyValue>;
+    fn osd_message(&mut self, text: &str, duration: Duration) -> Result<(), &'static str>;
+    fn command(&mut self, args: &[&str]) -> Result<(), &'static str>;
+}
+
+pub trait PropertyType: Sized {
+    const FORMAT: PropertyFormat;
+    fn from_value(value: PropertyValue) -> Option<Self>;
+}
+
+impl dyn MpvHandle {
+    pub fn get_property<T: PropertyType>(&self, name: impl AsRef<str>) -> Option<T> {
+        let value = self.get_property_value(name.as_ref(), T::FORMAT)?;
+        T::from_value(value)
+    }
+}
+
+impl PropertyType for bool {
+    const FORMAT: PropertyFormat = PropertyFormat::Flag;
+
+    fn from_value(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Flag(flag) => Some(flag),
+            _ => None
+        }
+    }
+}
+
+impl PropertyType for i64 {
+    const FORMAT: PropertyFormat = PropertyFormat::Int64;
+
+    fn from_value(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Int64(int) => Some(int),
+            _ => None
+        }
+    }
+}
+
+impl PropertyType for f64 {
+    const FORMAT: PropertyFormat = PropertyFormat::Double;
+
+    fn from_value(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::Double(double) => Some(double),
+            PropertyValue::Int64(int) => Some(int as f64),
+            _ => None
+        }
+    }
+}
+
+impl PropertyType for String {
+    const FORMAT: PropertyFormat = PropertyFormat::String;
+
+    fn from_value(value: PropertyValue) -> Option<Self> {
+        match value {
+            PropertyValue::String(string) => Some(string),
+            _ => None
+        }
+    }
+}
+
+impl PropertyFormat {
+    fn mpv_format(self) -> i32 {
+        match self {
+            PropertyFormat::None => 0,
+            PropertyFormat::Flag => bool::MPV_FORMAT,
+            PropertyFormat::Int64 => i64::MPV_FORMAT,
+            PropertyFormat::Double => f64::MPV_FORMAT,
+            PropertyFormat::String => String::MPV_FORMAT
+        }
+    }
+}
+
+impl PropertyValue {
+    // The format of the data isn't exposed, so every known one is tried
+    fn from_property(property: &Property) -> Self {
+        if let Some(flag) = property.data() {
+            return PropertyValue::Flag(flag);
+        }
+
+        if let Some(int) = property.data() {
+            return PropertyValue::Int64(int);
+        }
+
+        if let Some(double) = property.data() {
+            return PropertyValue::Double(double);
+        }
+
+        match property.data() {
+            Some(string) => PropertyValue::String(string),
+            None => PropertyValue::None
+        }
+    }
+}
+
+impl RawEvent {
+    fn from_event(event: Event) -> Self {
+        match event {
+            Event::None => RawEvent::None,
+            Event::Shutdown => RawEvent::Shutdown,
+            Event::FileLoaded => RawEvent::FileLoaded,
+            Event::PlaybackRestart => RawEvent::PlaybackRestart,
+            Event::ClientMessage(message) => RawEvent::ClientMessage(message.args()),
+            Event::PropertyChange(id, property) => RawEvent::PropertyChange(id, PropertyValue::from_property(&property)),
+            event => RawEvent::Other(event.to_string())
+        }
+    }
+}
+
+impl fmt::Display for RawEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RawEvent::None => "none",
+            RawEvent::Shutdown => "shutdown",
+            RawEvent::FileLoaded => "file-loaded",
+            RawEvent::PlaybackRestart => "playback-restart",
+            RawEvent::ClientMessage(_) => "client-message",
+            RawEvent::PropertyChange(..) => "property-change",
+            RawEvent::Other(name) => name
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl MpvHandle for Handle {
+    fn wait_event(&mut self, timeout: f64) -> RawEvent {
+        RawEvent::from_event(Handle::wait_event(self, timeout))
+    }
+
+    fn observe_property(&mut self, id: u64, name: &str, format: PropertyFormat) -> Result<(), &'static str> {
+        match Handle::observe_property(self, id, name, format.mpv_format()) {
+            Ok(()) => Ok(()),
+            Err(_) => Err("cannot observe property")
+        }
+    }
+
+    fn get_property_value(&self, name: &str, format: PropertyFormat) -> Option<PropertyValue> {
+        match format {
+            PropertyFormat::None => None,
+            PropertyFormat::Flag => Handle::get_property(self, name).ok().map(PropertyValue::Flag),
+            PropertyFormat::Int64 => Handle::get_property(self, name).ok().map(PropertyValue::Int64),
+            PropertyFormat::Double => Handle::get_property(self, name).ok().map(PropertyValue::Double),
+            PropertyFormat::String => Handle::get_property(self, name).ok().map(PropertyValue::String)
+        }
+    }
+
+    fn osd_message(&mut self, text: &str, duration: Duration) -> Result<(), &'static str> {
+        match Handle::osd_message(self, text, duration) {
+            Ok(()) => Ok(()),
+            Err(_) => Err("cannot print OSD message")
+        }
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<(), &'static str> {
+        match Handle::command(self, args) {
+            Ok(()) => Ok(()),
+            Err(_) => Err("cannot run mpv command")
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use super::handle::{MpvHandle, RawEvent, PropertyFormat, PropertyValue};
+
+// Stand-in for mpv that plays back a programmed sequence of events
+// and answers property reads from a table that can change between events.
+// Events can be delayed, relative to the creation of the script
+pub struct ScriptedMpv {
+    script: VecDeque<ScriptStep>,
+    properties: HashMap<String, PropertyValue>,
+    observed: Vec<(u64, String)>,
+    osd_messages: Vec<String>,
+    commands: Vec<Vec<String>>,
+    start: Instant
+}
+
+enum ScriptStep {
+    Event(RawEvent, Duration),
+    Property(String, Option<PropertyValue>)
+}
+
+impl ScriptedMpv {
+    pub fn new() -> Self {
+        Self {
+            script: VecDeque::new(),
+            properties: HashMap::new(),
+            observed: Vec::new(),
+            osd_messages: Vec::new(),
+            commands: Vec::new(),
+            start: Instant::now()
+        }
+    }
+
+    // Traces always give the time, scripts written by hand mostly don't need it
+    #[cfg(test)]
+    pub fn push_event(&mut self, event: RawEvent) {
+        self.push_event_at(event, Duration::ZERO);
+    }
+
+    // Not returned before the given time has passed since the script was created
+    pub fn push_event_at(&mut self, event: RawEvent, time: Duration) {
+        self.script.push_back(ScriptStep::Event(event, time));
+    }
+
+    // Takes effect once the events pushed before it have been consumed
+    pub fn push_property(&mut self, name: &str, value: PropertyValue) {
+        self.script.push_back(ScriptStep::Property(name.to_string(), Some(value)));
+    }
+
+    // Makes reads of the property fail, like for an unavailable property in mpv
+    pub fn remove_property(&mut self, name: &str) {
+        self.script.push_back(ScriptStep::Property(name.to_string(), None));
+    }
+
+    // What the event queue did with mpv, for checking in tests
+    #[cfg(test)]
+    pub fn observed(&self) -> &[(u64, String)] {
+        &self.observed
+    }
+
+    #[cfg(test)]
+    pub fn osd_messages(&self) -> &[String] {
+        &self.osd_messages
+    }
+
+    #[cfg(test)]
+    pub fn commands(&self) -> &[Vec<String>] {
+        &self.commands
+    }
+}
+
+impl MpvHandle for ScriptedMpv {
+    // Shuts down once the script runs out, so the event loop finishes
+    fn wait_event(&mut self, timeout: f64) -> RawEvent {
+        while let Some(step) = self.script.pop_front() {
+            match step {
+                ScriptStep::Event(event, time) => {
+                    let elapsed = self.start.elapsed();
+                    if time <= elapsed {
+                        return event;
+                    }
+
+                    // Times out like mpv would, if the event isn't due yet
+                    let timeout = Duration::from_secs_f64(timeout);
+                    thread::sleep((time - elapsed).min(timeout));
+                    if time > self.start.elapsed() {
+                        self.script.push_front(ScriptStep::Event(event, time));
+                        return RawEvent::None;
+                    }
+
+                    return event;
+                }
+                ScriptStep::Property(name, Some(value)) => {
+                    self.properties.insert(name, value);
+                }
+                ScriptStep::Property(name, None) => {
+                    self.properties.remove(&name);
+                }
+            }
+        }
+
+        RawEvent::Shutdown
+    }
+
+    fn observe_property(&mut self, id: u64, name: &str, _format: PropertyFormat) -> Result<(), &'static str> {
+        self.observed.push((id, name.to_string()));
+        Ok(())
+    }
+
+    fn get_property_value(&self, name: &str, _format: PropertyFormat) -> Option<PropertyValue> {
+        self.properties.get(name).cloned()
+    }
+
+    fn osd_message(&mut self, text: &str, _duration: Duration) -> Result<(), &'static str> {
+        self.osd_messages.push(text.to_string());
+        Ok(())
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<(), &'static str> {
+        self.commands.push(args.iter().map(|arg| arg.to_string()).collect());
+        Ok(())
+    }
+}
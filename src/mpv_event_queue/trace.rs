@@ -0,0 +1,346 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{Write, LineWriter};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use super::handle::{MpvHandle, RawEvent, PropertyFormat, PropertyValue};
+use super::scripted_mpv::ScriptedMpv;
+
+// One line of a trace, times are in seconds since the trace was started.
+// A missing value means the property couldn't be read.
+// Replaying only needs the time of events, property reads are ordered by them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TraceEntry {
+    Event {
+        time: f64,
+        event: RawEvent
+    },
+    Property {
+        property: String,
+        value: Option<PropertyValue>
+    }
+}
+
+#[derive(Serialize)]
+struct EventEntry<'a> {
+    time: f64,
+    event: &'a RawEvent
+}
+
+#[derive(Serialize)]
+struct PropertyEntry<'a> {
+    time: f64,
+    property: &'a str,
+    value: &'a Option<PropertyValue>
+}
+
+// Passes everything through to mpv, writing the events and property values
+// the event queue sees to a JSON Lines file
+pub struct TracingMpv {
+    mpv: Box<dyn MpvHandle>,
+    writer: RefCell<LineWriter<File>>,
+    start: Instant
+}
+
+impl TracingMpv {
+    pub fn new(mpv: Box<dyn MpvHandle>, path: &str) -> Result<Self, &'static str> {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(_) => return Err("Failed to create trace file")
+        };
+
+        Ok(Self {
+            mpv,
+            writer: RefCell::new(LineWriter::new(file)),
+            start: Instant::now()
+        })
+    }
+
+    // Tracing is best effort, a failed write shouldn't stop the plugin
+    fn write_entry(&self, entry: &impl Serialize) {
+        if let Ok(line) = serde_json::to_string(entry) {
+            let _ = writeln!(self.writer.borrow_mut(), "{line}");
+        }
+    }
+
+    fn get_time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+impl MpvHandle for TracingMpv {
+    // Timeouts are left out, replaying the timing of the events recreates them
+    fn wait_event(&mut self, timeout: f64) -> RawEvent {
+        let event = self.mpv.wait_event(timeout);
+        if !matches!(event, RawEvent::None) {
+            self.write_entry(&EventEntry { time: self.get_time(), event: &event });
+        }
+
+        event
+    }
+
+    fn observe_property(&mut self, id: u64, name: &str, format: PropertyFormat) -> Result<(), &'static str> {
+        self.mpv.observe_property(id, name, format)
+    }
+
+    fn get_property_value(&self, name: &str, format: PropertyFormat) -> Option<PropertyValue> {
+        let value = self.mpv.get_property_value(name, format);
+        self.write_entry(&PropertyEntry { time: self.get_time(), property: name, value: &value });
+
+        value
+    }
+
+    fn osd_message(&mut self, text: &str, duration: Duration) -> Result<(), &'static str> {
+        self.mpv.osd_message(text, duration)
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<(), &'static str> {
+        self.mpv.command(args)
+    }
+}
+
+// Turns a trace back into a script with the original timing.
+// Properties are only read while converting the event before them,
+// so they are scripted to change right before that event is returned
+pub fn load_trace(path: &str) -> Result<ScriptedMpv, &'static str> {
+    let trace = match fs::read_to_string(path) {
+        Ok(trace) => trace,
+        Err(_) => return Err("Failed to read trace file")
+    };
+
+    let mut mpv = ScriptedMpv::new();
+    let mut pending_event = None;
+
+    for line in trace.lines().filter(|line| !line.trim().is_empty()) {
+        let entry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(_) => return Err("Failed to parse trace file")
+        };
+
+        match entry {
+            TraceEntry::Event { time, event } => {
+                let time = parse_time(time)?;
+                if let Some((event, time)) = pending_event.replace((event, time)) {
+                    mpv.push_event_at(event, time);
+                }
+            }
+            TraceEntry::Property { property, value: Some(value) } => mpv.push_property(&property, value),
+            TraceEntry::Property { property, value: None } => mpv.remove_property(&property)
+        }
+    }
+
+    if let Some((event, time)) = pending_event {
+        mpv.push_event_at(event, time);
+    }
+
+    Ok(mpv)
+}
+
+fn parse_time(time: f64) -> Result<Duration, &'static str> {
+    match Duration::try_from_secs_f64(time) {
+        Ok(time) => Ok(time),
+        Err(_) => Err("Invalid event time in trace file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use std::rc::Rc;
+    use serde_json::{json, Value};
+    use crate::config::Config;
+    use crate::discord_client::DiscordClient;
+    use crate::discord_client::fake_discord::FakeDiscord;
+    use crate::logging::{Logger, LogLevel};
+    use crate::mpv_event_queue::events::MpvEventHandler;
+    use crate::test_utils::TempDir;
+    use crate::mpv_event_queue::{MpvEventQueue, REPL_PAUSE_PROP, REPL_METADATA_PROP};
+    use crate::mpv_event_queue::events::MpvEvent;
+    use super::*;
+
+    fn string(value: &str) -> PropertyValue {
+        PropertyValue::String(value.to_string())
+    }
+
+    // Enough of each event to tell whether the replay read the same properties for it
+    fn describe(event: &MpvEvent) -> String {
+        match event {
+            MpvEvent::FileLoaded(file_info) => format!(
+                "file-loaded {} {:?} {:?} {:?}",
+                file_info.filename,
+                file_info.duration,
+                file_info.metadata.artist,
+                file_info.metadata.title
+            ),
+            MpvEvent::MetadataChange(metadata) => format!("metadata {:?}", metadata.title),
+            MpvEvent::Play(time) => format!("play {:?} {}", time.remaining, time.position),
+            MpvEvent::Pause => "pause".to_string(),
+            MpvEvent::Seek(time) => format!("seek {:?} {}", time.remaining, time.position),
+            MpvEvent::Exit => "exit".to_string(),
+            _ => "other".to_string()
+        }
+    }
+
+    // Records a trace of the queue running until mpv shuts down
+    fn run_traced(mpv: ScriptedMpv, path: &str) -> Vec<String> {
+        let tracing = TracingMpv::new(Box::new(mpv), path).unwrap();
+        let mut queue = MpvEventQueue::new(Box::new(tracing), Rc::new(Logger::new(LogLevel::None))).unwrap();
+
+        let mut events = Vec::new();
+        loop {
+            let Some(event) = queue.next_event() else {
+                continue;
+            };
+
+            events.push(describe(&event));
+            if matches!(event, MpvEvent::Exit) {
+                return events;
+            }
+        }
+    }
+
+    // Trace lines without their times, which differ between runs
+    fn read_entries(path: &str) -> Vec<Value> {
+        fs::read_to_string(path).unwrap()
+                                .lines()
+                                .map(|line| {
+                                    let mut entry: Value = serde_json::from_str(line).unwrap();
+                                    entry.as_object_mut().unwrap().remove("time");
+                                    entry
+                                })
+                                .collect()
+    }
+
+    #[test]
+    fn replays_recorded_session() {
+        let mut mpv = ScriptedMpv::new();
+        mpv.push_property("filename", string("song.flac"));
+        mpv.push_property("duration", PropertyValue::Double(200.0));
+        mpv.push_property("metadata/by-key/artist", string("Artist"));
+        mpv.push_property("metadata/by-key/title", string("Song"));
+        mpv.push_event(RawEvent::FileLoaded);
+        mpv.push_property("playtime-remaining", PropertyValue::Double(180.0));
+        mpv.push_property("time-pos", PropertyValue::Double(20.0));
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(false)));
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(true)));
+
+        // Each event has to see the values from before it, not the final ones
+        mpv.push_property("playtime-remaining", PropertyValue::Double(100.0));
+        mpv.push_property("time-pos", PropertyValue::Double(100.0));
+        mpv.push_event(RawEvent::PlaybackRestart);
+        mpv.push_property("metadata/by-key/title", string("Next Song"));
+        mpv.remove_property("time-pos");
+        mpv.push_event(RawEvent::PropertyChange(REPL_METADATA_PROP, PropertyValue::None));
+        mpv.push_event(RawEvent::PropertyChange(REPL_PAUSE_PROP, PropertyValue::Flag(false)));
+
+        let dir = TempDir::new("trace");
+        let recorded_path = dir.file("recorded.jsonl");
+        let recorded_events = run_traced(mpv, &recorded_path);
+        assert_eq!(recorded_events, vec![
+            "file-loaded song.flac Some(200.0) Some(\"Artist\") Some(\"Song\")",
+            "play Some(180) 20",
+            "pause",
+            "seek Some(100) 100",
+            "metadata Some(\"Next Song\")",
+            "play Some(100) 0",
+            "exit"
+        ]);
+
+        let replayed_path = dir.file("replayed.jsonl");
+        let replayed_events = run_traced(load_trace(&recorded_path).unwrap(), &replayed_path);
+        assert_eq!(replayed_events, recorded_events);
+
+        let recorded = read_entries(&recorded_path);
+        assert!(recorded.iter().any(|entry| entry["property"] == "time-pos" && entry["value"].is_null()));
+        assert_eq!(read_entries(&replayed_path), recorded);
+    }
+
+    #[test]
+    fn rejects_invalid_trace() {
+        let dir = TempDir::new("trace");
+        let path = dir.file("invalid.jsonl");
+        fs::write(&path, "{\"time\": 0.0, \"event\": \"file-loaded\"}\nnot json\n").unwrap();
+        assert!(load_trace(&path).is_err());
+
+        fs::write(&path, "{\"time\": -1.0, \"event\": \"file-loaded\"}\n").unwrap();
+        assert!(load_trace(&path).is_err());
+
+        assert!(load_trace(&dir.file("missing.jsonl")).is_err());
+    }
+
+    // A session as TracingMpv writes it: playing a song, pausing it and the tags changing
+    const SESSION_TRACE: &str = r#"
+{"time":0.0,"event":"file-loaded"}
+{"time":0.0,"property":"filename","value":{"string":"song.flac"}}
+{"time":0.0,"property":"path","value":{"string":"/music/song.flac"}}
+{"time":0.0,"property":"playlist-pos-1","value":{"int64":1}}
+{"time":0.0,"property":"playlist-count","value":{"int64":12}}
+{"time":0.0,"property":"chapter-metadata/title","value":null}
+{"time":0.0,"property":"duration","value":{"double":200.0}}
+{"time":0.0,"property":"metadata/by-key/artist","value":{"string":"Artist"}}
+{"time":0.0,"property":"metadata/by-key/album","value":{"string":"Album"}}
+{"time":0.0,"property":"metadata/by-key/title","value":{"string":"Title"}}
+{"time":0.01,"event":{"property-change":[1,{"flag":false}]}}
+{"time":0.01,"property":"playtime-remaining","value":{"double":180.4}}
+{"time":0.01,"property":"time-pos","value":{"double":19.6}}
+{"time":0.02,"event":{"property-change":[1,{"flag":true}]}}
+{"time":0.03,"event":{"property-change":[5,"none"]}}
+{"time":0.03,"property":"metadata/by-key/artist","value":{"string":"Artist"}}
+{"time":0.03,"property":"metadata/by-key/album","value":{"string":"Album"}}
+{"time":0.03,"property":"metadata/by-key/title","value":{"string":"Next Title"}}
+{"time":0.04,"event":"shutdown"}
+"#;
+
+    #[test]
+    fn replays_trace_to_discord() {
+        let dir = TempDir::new("trace");
+        let trace_path = dir.file("trace.jsonl");
+        fs::write(&trace_path, SESSION_TRACE).unwrap();
+
+        let socket_path = dir.path().join("discord-ipc-0");
+        let discord = FakeDiscord::bind(&socket_path).unwrap();
+        let config = Config {
+            active: true,
+            cover_art: false,
+            cover_art_cache: false,
+            ..Config::default()
+        };
+        let logger = Rc::new(Logger::new(LogLevel::None));
+        let mut client = DiscordClient::with_socket("1234", Some(socket_path), &config, Rc::clone(&logger)).unwrap();
+        let mut queue = MpvEventQueue::from_trace(&trace_path, logger).unwrap();
+
+        loop {
+            let Some(event) = queue.next_event() else {
+                continue;
+            };
+
+            let exit = matches!(event, MpvEvent::Exit);
+            client.handle_event(event).unwrap();
+            if exit {
+                break;
+            }
+        }
+
+        // The first activity is the empty one sent when connecting
+        let activities = discord.wait_for_activities(5);
+        assert_eq!(activities.len(), 5);
+
+        let loaded = json!({
+            "type": 2,
+            "details": "Title",
+            "state": "by Artist on Album",
+            "assets": { "large_image": "logo", "large_text": "Title" },
+            "timestamps": {}
+        });
+        assert_eq!(activities[1], loaded);
+
+        let end = activities[2]["timestamps"]["end"].as_i64().unwrap();
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        assert!((end - (now + 180)).abs() <= 2, "{end} isn't close to {}", now + 180);
+
+        assert_eq!(activities[3], loaded);
+        assert_eq!(activities[4]["details"], "Next Title");
+        assert_eq!(activities[4]["timestamps"], json!({}));
+    }
+}
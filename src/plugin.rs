@@ -1,28 +1,49 @@
+use std::env;
 use std::rc::Rc;
-use mpv_client::mpv_handle;
+use mpv_client::{Handle, mpv_handle};
 use crate::logging::{self, Logger};
 use crate::config::Config;
+use crate::config::watcher::ConfigWatcher;
+use crate::config::paths::ConfigPaths;
 use crate::discord_client::DiscordClient;
 use crate::mpv_event_queue::MpvEventQueue;
+use crate::mpv_event_queue::commands::Command;
 use crate::mpv_event_queue::events::{MpvEventHandler, MpvRequester, MpvEvent, MpvRequest};
 
 pub struct RPCPlugin {
     logger: Rc<Logger>,
     mpv: MpvEventQueue,
     discord: DiscordClient,
+    config: Config,
+    config_watcher: ConfigWatcher
 }
 
 impl RPCPlugin {
-    pub fn new(handle: *mut mpv_handle, client_id: &str) -> Result<Self, &'static str> {
+    pub fn new(handle: *mut mpv_handle) -> Result<Self, &'static str> {
         let logger = Rc::new(Logger::from_env());
-        let config = Config::from_config_file(&logger);
-        let mpv = MpvEventQueue::from_ptr(handle, Rc::clone(&logger))?;
-        let discord = DiscordClient::new(client_id, config.active, config.cover_art, Rc::clone(&logger))?;
+        // The event queue is set up according to the config, so the options are read beforehand
+        let script_opts = Handle::from_ptr(handle).get_property::<String, _>("script-opts").ok();
+        let config_paths = ConfigPaths::from_mpv(handle);
+        let (config, issues) = Config::from_config_file(&config_paths, script_opts.as_deref(), &logger);
+        let mut mpv = match env::var("MPV_RPC_REPLAY") {
+            Ok(path) => MpvEventQueue::from_trace(&path, Rc::clone(&logger))?,
+            Err(_) => MpvEventQueue::from_ptr(handle, config.get_trace_path().as_deref(), Rc::clone(&logger))?
+        };
+        let discord = DiscordClient::new(&config.application_id, &config, Rc::clone(&logger))?;
+        let config_watcher = ConfigWatcher::new(config_paths.get_watched());
+
+        if !issues.is_empty() {
+            if let Err(e) = mpv.display_config_issues("Discord RPC config has errors", &issues) {
+                logging::error!(logger, "Failed to display config issues: {e}");
+            }
+        }
 
         Ok(Self {
             logger,
             mpv,
-            discord
+            discord,
+            config,
+            config_watcher
         })
     }
 
@@ -39,6 +60,18 @@ impl RPCPlugin {
                 }
             }
 
+            if self.config_watcher.has_changed() {
+                self.reload_config();
+            }
+
+            if let Err(e) = self.discord.update_connection() {
+                logging::error!(self.logger, "Failed to reconnect to Discord: {e}");
+            }
+
+            if let Err(e) = self.discord.update_cover_art() {
+                logging::error!(self.logger, "Failed to update cover art: {e}");
+            }
+
             let request = self.discord.next_request();
             match request {
                 None => (),
@@ -48,6 +81,11 @@ impl RPCPlugin {
     }
 
     fn handle_event(&mut self, event: MpvEvent) -> bool {
+        if let MpvEvent::Command(Command::ReloadConfig) = event {
+            self.reload_config();
+            return false;
+        }
+
         let exit = matches!(event, MpvEvent::Exit);
 
         if let Err(e) = self.discord.handle_event(event) {
@@ -57,7 +95,39 @@ impl RPCPlugin {
         exit
     }
 
-    fn handle_request(&self, request: MpvRequest) {
+    // Tracing is only set up at startup, everything else is applied right away
+    fn reload_config(&mut self) {
+        logging::info!(self.logger, "Reloading config");
+        let script_opts = self.mpv.get_script_opts();
+        let (config, issues) = match Config::reload_config_file(&self.config.paths, script_opts.as_deref(), &self.logger) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.display_config_issues("Discord RPC config is invalid, keeping the previous one", &[e]);
+                return;
+            }
+        };
+
+        // Nothing has been applied on errors, the next reload is compared with the previous config
+        if let Err(e) = self.discord.set_config(&config, &self.config) {
+            logging::error!(self.logger, "Failed to apply config: {e}");
+            self.display_config_issues("Discord RPC config couldn't be applied, keeping the previous one", &[e.to_string()]);
+            return;
+        }
+
+        self.config = config;
+        match issues.is_empty() {
+            true => self.handle_request(MpvRequest::OSDMessage("Discord RPC config reloaded")),
+            false => self.display_config_issues("Discord RPC config reloaded with errors", &issues)
+        }
+    }
+
+    fn display_config_issues(&mut self, title: &str, issues: &[String]) {
+        if let Err(e) = self.mpv.display_config_issues(title, issues) {
+            logging::error!(self.logger, "Failed to display config issues: {e}");
+        }
+    }
+
+    fn handle_request(&mut self, request: MpvRequest) {
         if let Err(e) = self.mpv.handle_request(request) {
             logging::error!(self.logger, "Failed to handle mpv request: {e}");
         }
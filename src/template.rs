@@ -0,0 +1,282 @@
+use std::fmt;
+use std::iter::{Enumerate, Peekable};
+use std::str::Chars;
+
+// Templates are plain text with placeholders:
+//   {name}           replaced with the value of the field
+//   {name|other}     first of the fields that has a value
+//   {? text {name}}  optional segment, removed when any field inside has no value
+//   {{ and }}        literal braces, only {{ inside optional segments
+
+pub struct TemplateError {
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+enum Segment {
+    Text(String),
+    Field(Vec<String>),
+    Optional(Vec<Segment>)
+}
+
+pub struct Template {
+    segments: Vec<Segment>
+}
+
+struct Parser<'a> {
+    chars: Peekable<Enumerate<Chars<'a>>>,
+    length: usize,
+    fields: &'a [&'a str]
+}
+
+impl Template {
+    pub fn parse(source: &str, fields: &[&str]) -> Result<Self, TemplateError> {
+        let mut parser = Parser {
+            chars: source.chars().enumerate().peekable(),
+            length: source.chars().count(),
+            fields
+        };
+
+        let segments = parser.parse_segments(false)?;
+        Ok(Self {
+            segments
+        })
+    }
+
+    // Fields without a value render as empty text outside of optional segments
+    pub fn render<F>(&self, get_value: F) -> String
+    where
+        F: Fn(&str) -> Option<String>
+    {
+        let mut output = String::new();
+        for segment in &self.segments {
+            let value = Template::render_segment(segment, &get_value);
+            output += &value.unwrap_or_default();
+        }
+
+        output
+    }
+
+    fn render_segments<F>(segments: &[Segment], get_value: &F) -> Option<String>
+    where
+        F: Fn(&str) -> Option<String>
+    {
+        let mut output = String::new();
+        for segment in segments {
+            output += &Template::render_segment(segment, get_value)?;
+        }
+
+        Some(output)
+    }
+
+    fn render_segment<F>(segment: &Segment, get_value: &F) -> Option<String>
+    where
+        F: Fn(&str) -> Option<String>
+    {
+        match segment {
+            Segment::Text(text) => Some(text.clone()),
+            Segment::Field(names) => names.iter()
+                                          .filter_map(|name| get_value(name))
+                                          .find(|value| !value.is_empty()),
+
+            // A missing field inside only removes the optional segment itself
+            Segment::Optional(segments) => Some(Template::render_segments(segments, get_value).unwrap_or_default())
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn parse_segments(&mut self, nested: bool) -> Result<Vec<Segment>, TemplateError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+
+        while let Some((index, char)) = self.chars.next() {
+            match char {
+                '{' if self.next_is('{') => text.push('{'),
+
+                // Inside optional segments a closing brace always ends the segment
+                '}' if nested => {
+                    Parser::push_text(&mut segments, &mut text);
+                    return Ok(segments);
+                }
+                '}' if self.next_is('}') => text.push('}'),
+                '}' => return Err(Parser::error(index, "unmatched '}', use '}}' for a literal brace")),
+                '{' => {
+                    Parser::push_text(&mut segments, &mut text);
+                    segments.push(self.parse_placeholder(index)?);
+                }
+                char => text.push(char)
+            }
+        }
+
+        if nested {
+            return Err(Parser::error(self.length, "unclosed optional segment"));
+        }
+
+        Parser::push_text(&mut segments, &mut text);
+        Ok(segments)
+    }
+
+    fn parse_placeholder(&mut self, start: usize) -> Result<Segment, TemplateError> {
+        if self.next_is('?') {
+            let segments = self.parse_segments(true)?;
+            return Ok(Segment::Optional(segments));
+        }
+
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, char)) => name.push(char),
+                None => return Err(Parser::error(start, "unclosed placeholder"))
+            }
+        }
+
+        let names: Vec<String> = name.split('|')
+                                     .map(|name| name.trim().to_string())
+                                     .collect();
+
+        for name in &names {
+            if name.is_empty() {
+                return Err(Parser::error(start, "empty placeholder"));
+            }
+
+            if !self.fields.contains(&name.as_str()) {
+                return Err(TemplateError {
+                    column: start + 1,
+                    message: format!("unknown placeholder '{name}'")
+                });
+            }
+        }
+
+        Ok(Segment::Field(names))
+    }
+
+    fn next_is(&mut self, expected: char) -> bool {
+        self.chars.next_if(|(_, char)| *char == expected).is_some()
+    }
+
+    fn push_text(segments: &mut Vec<Segment>, text: &mut String) {
+        if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(text)));
+        }
+    }
+
+    fn error(index: usize, message: &str) -> TemplateError {
+        TemplateError {
+            column: index + 1,
+            message: message.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: [&str; 4] = ["artist", "album", "title", "track"];
+
+    fn render(source: &str, values: &[(&str, &str)]) -> String {
+        let template = match Template::parse(source, &FIELDS) {
+            Ok(template) => template,
+            Err(e) => panic!("cannot parse \"{source}\": {e}")
+        };
+
+        template.render(|name| {
+            values.iter()
+                  .find(|(field, _)| *field == name)
+                  .map(|(_, value)| value.to_string())
+        })
+    }
+
+    fn parse_error(source: &str) -> String {
+        match Template::parse(source, &FIELDS) {
+            Ok(_) => panic!("\"{source}\" should be invalid"),
+            Err(e) => e.to_string()
+        }
+    }
+
+    #[test]
+    fn replaces_fields() {
+        assert_eq!(render("{title} - {artist}", &[("title", "Title"), ("artist", "Artist")]), "Title - Artist");
+        assert_eq!(render("plain text", &[]), "plain text");
+        assert_eq!(render("", &[]), "");
+    }
+
+    #[test]
+    fn missing_fields_render_empty() {
+        assert_eq!(render("[{track}] {title}", &[("title", "Title")]), "[] Title");
+    }
+
+    #[test]
+    fn optional_segment_disappears_without_its_fields() {
+        let source = "{title}{? by {artist}}{? on {album}}";
+        assert_eq!(render(source, &[("title", "Title"), ("artist", "Artist"), ("album", "Album")]), "Title by Artist on Album");
+        assert_eq!(render(source, &[("title", "Title"), ("album", "Album")]), "Title on Album");
+        assert_eq!(render(source, &[("title", "Title")]), "Title");
+    }
+
+    #[test]
+    fn optional_segment_needs_every_field() {
+        let source = "{?{artist} - {album}}";
+        assert_eq!(render(source, &[("artist", "Artist"), ("album", "Album")]), "Artist - Album");
+        assert_eq!(render(source, &[("artist", "Artist")]), "");
+    }
+
+    #[test]
+    fn empty_values_count_as_missing() {
+        assert_eq!(render("{title}{? by {artist}}", &[("title", "Title"), ("artist", "")]), "Title");
+    }
+
+    #[test]
+    fn fallback_uses_first_field_with_value() {
+        let source = "{title|album|artist}";
+        assert_eq!(render(source, &[("title", "Title"), ("album", "Album")]), "Title");
+        assert_eq!(render(source, &[("album", "Album"), ("artist", "Artist")]), "Album");
+        assert_eq!(render(source, &[("title", ""), ("artist", "Artist")]), "Artist");
+        assert_eq!(render(source, &[]), "");
+        assert_eq!(render("{? ({title | album})}", &[("album", "Album")]), " (Album)");
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(render("{{title}}", &[("title", "Title")]), "{title}");
+        assert_eq!(render("{{{title}}}", &[("title", "Title")]), "{Title}");
+        assert_eq!(render("{?{{{artist}}", &[("artist", "Artist")]), "{Artist");
+    }
+
+    #[test]
+    fn reports_unclosed_placeholder() {
+        assert_eq!(parse_error("{title"), "column 1: unclosed placeholder");
+        assert_eq!(parse_error("by {artist"), "column 4: unclosed placeholder");
+    }
+
+    #[test]
+    fn reports_unclosed_optional_segment() {
+        assert_eq!(parse_error("{? by {artist}"), "column 15: unclosed optional segment");
+    }
+
+    #[test]
+    fn reports_unmatched_closing_brace() {
+        assert_eq!(parse_error("a}b"), "column 2: unmatched '}', use '}}' for a literal brace");
+        assert_eq!(parse_error("{title}}"), "column 8: unmatched '}', use '}}' for a literal brace");
+    }
+
+    #[test]
+    fn reports_empty_placeholder() {
+        assert_eq!(parse_error("{}"), "column 1: empty placeholder");
+        assert_eq!(parse_error("ab {title|}"), "column 4: empty placeholder");
+    }
+
+    #[test]
+    fn reports_unknown_placeholder() {
+        assert_eq!(parse_error("x {nope}"), "column 3: unknown placeholder 'nope'");
+        assert_eq!(parse_error("{? {title|nope}}"), "column 4: unknown placeholder 'nope'");
+    }
+}
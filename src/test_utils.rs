@@ -0,0 +1,74 @@
+// Helpers shared by the tests of several modules
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::mpv_event_queue::events::{FileInfo, FileMetadata, MediaKind};
+
+static TEMP_DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Directory of its own for each test, removed with everything in it when dropped
+pub struct TempDir {
+    path: PathBuf
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let count = TEMP_DIR_COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("mpv-rpc-{name}-{}-{count}", process::id()));
+        fs::create_dir_all(&path).expect("cannot create temp dir");
+
+        Self {
+            path
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Path of a file in the directory, as the config stores them
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// Untagged video, tests fill in what they need
+pub fn file_info(path: &str) -> FileInfo {
+    FileInfo {
+        filename: path.rsplit('/').next().unwrap_or_default().to_string(),
+        path: path.to_string(),
+        playlist_pos: None,
+        playlist_count: None,
+        chapter: None,
+        duration: None,
+        media_kind: MediaKind::Video,
+        metadata: FileMetadata::default(),
+        media_name: None
+    }
+}
+
+// Tagged song from a playlist
+pub fn song_file_info() -> FileInfo {
+    FileInfo {
+        playlist_pos: Some(1),
+        playlist_count: Some(12),
+        duration: Some(200.0),
+        media_kind: MediaKind::Audio,
+        metadata: FileMetadata {
+            artist: Some("Artist".to_string()),
+            album: Some("Album".to_string()),
+            title: Some("Title".to_string()),
+            ..FileMetadata::default()
+        },
+        ..file_info("/music/song.flac")
+    }
+}
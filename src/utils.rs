@@ -13,3 +13,13 @@ pub fn truncate_string_fmt(current: &mut String, length: usize) {
     truncate_string(current, length - 3);
     current.push_str("...");
 }
+
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    match hours {
+        0 => format!("{minutes}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}")
+    }
+}